@@ -0,0 +1,109 @@
+use rand::Rng;
+
+use maze::Maze;
+use posn::Posn;
+use tile::Tile;
+
+fn is_dead_end(maze: &Maze, pos: Posn) -> bool {
+    maze[&pos] == Tile::Floor &&
+    maze.neighbours(pos).iter().filter(|&p| maze[p] != Tile::Wall).count() == 1
+}
+
+/// Knocks out walls to remove `percent` of the dead ends in a maze, turning
+/// a perfect maze into one with loops.
+///
+/// This works on the tile map, so it handles loaded mazes as well as
+/// generated ones. Walls are only removed between two floor tiles, and
+/// never on the border.
+pub fn braid<R: Rng>(maze: &mut Maze, percent: u32, rng: &mut R) {
+    let topology = maze.topology();
+    let (rows, cols) = maze.bounds();
+    let wrap = maze.wrap;
+    let interior = |p: &Posn| wrap || p.row > 0 && p.col > 0 && p.row < rows as i32 - 1 &&
+                              p.col < cols as i32 - 1;
+
+    let mut dead_ends = vec![];
+    for row in 0..rows as i32 {
+        for col in 0..cols as i32 {
+            let pos = Posn { row, col };
+            if is_dead_end(maze, pos) {
+                dead_ends.push(pos);
+            }
+        }
+    }
+    let target = (dead_ends.len() * percent.min(100) as usize + 50) / 100;
+    info!("Braiding {} of {} dead ends", target, dead_ends.len());
+    rng.shuffle(&mut dead_ends);
+
+    let mut removed = 0;
+    for pos in dead_ends {
+        if removed >= target {
+            break;
+        }
+        // Joining two dead ends earlier may have already fixed this one.
+        if !is_dead_end(maze, pos) {
+            continue;
+        }
+        let mut candidates = vec![];
+        for &dir in topology.directions() {
+            let wall = maze.step(pos, dir);
+            let beyond = maze.step(wall, dir);
+            if interior(&wall) && maze[&wall] == Tile::Wall && maze[&beyond] == Tile::Floor {
+                candidates.push((wall, beyond));
+            }
+        }
+        // Preferring another dead end removes two for the price of one.
+        let best: Vec<(Posn, Posn)> = candidates.iter()
+            .cloned()
+            .filter(|&(_, beyond)| is_dead_end(maze, beyond))
+            .collect();
+        let choice = rng.choose(&best).or_else(|| rng.choose(&candidates)).cloned();
+        if let Some((wall, beyond)) = choice {
+            removed += if is_dead_end(maze, beyond) { 2 } else { 1 };
+            maze.set(&wall, Tile::Floor);
+        }
+    }
+}
+
+#[test]
+fn test_braid_removes_dead_ends() {
+    use generator::Algorithm;
+    use topology::Shape;
+    use pathfind::pathfind;
+    use rng::GameRng;
+    use tower::{Location, Tower};
+
+    let count_dead_ends = |maze: &Maze| {
+        let (rows, cols) = maze.bounds();
+        let mut count = 0;
+        for row in 0..rows as i32 {
+            for col in 0..cols as i32 {
+                if is_dead_end(maze, Posn { row, col }) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    };
+
+    let mut rng = GameRng::new(7);
+    let maze = Algorithm::RecursiveBacktracker.generate(Shape::Square, 12, 12, false, &mut rng);
+    let before = count_dead_ends(&maze);
+
+    let mut unchanged = maze.clone();
+    braid(&mut unchanged, 0, &mut rng);
+    assert_eq!(unchanged, maze);
+
+    let mut half = maze.clone();
+    braid(&mut half, 50, &mut rng);
+    assert!(count_dead_ends(&half) <= before / 2 + 1);
+
+    let mut full = maze.clone();
+    braid(&mut full, 100, &mut rng);
+    assert_eq!(count_dead_ends(&full), 0);
+    let from = Location {
+        floor: 0,
+        pos: Posn { row: 23, col: 23 },
+    };
+    assert!(!pathfind(&Tower::from(full), from).is_empty());
+}
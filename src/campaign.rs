@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::Path;
+
+use level::split_header;
+use sokoban;
+
+/// Levels played one after the other, remembering how far you've got.
+///
+/// A campaign is either a directory of level files, played in name order,
+/// or a manifest with one level per line. Each line is a level file and/or
+/// the command line options to play it with, which go after the ones the
+/// game was started with:
+///
+/// ```text
+/// # Start gently.
+/// ambush.txt
+/// --generator prim --size 8 --trolls 3
+/// cave.pgm --trolls 2 --braid 50
+/// ```
+///
+/// Level files are found relative to the manifest. A Sokoban collection
+/// (an `.xsb` file) is a campaign too, played level by level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Campaign {
+    /// The options for each level.
+    pub levels: Vec<Vec<String>>,
+    progress_file: String,
+}
+
+impl Campaign {
+    pub fn load(path: &str) -> Result<Campaign, String> {
+        let metadata = fs::metadata(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+        if metadata.is_dir() {
+            let mut files = vec![];
+            for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
+                let entry = entry.map_err(|e| e.to_string())?;
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden && entry.path().is_file() {
+                    files.push(entry.path().to_string_lossy().into_owned());
+                }
+            }
+            files.sort();
+            if files.is_empty() {
+                return Err(format!("There are no levels in {}", path));
+            }
+            return Ok(Campaign {
+                levels: files.into_iter().map(|f| vec!["--maze".to_owned(), f]).collect(),
+                progress_file: Path::new(path).join(".progress").to_string_lossy().into_owned(),
+            });
+        }
+
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+        if sokoban::is_collection(path) {
+            let count = sokoban::parse(&text)
+                .map_err(|e| format!("Couldn't load {}: {}", path, e))?
+                .len();
+            return Ok(Campaign {
+                levels: (1..count + 1)
+                    .map(|level| {
+                        vec!["--maze".to_owned(),
+                             path.to_owned(),
+                             "--level".to_owned(),
+                             level.to_string()]
+                    })
+                    .collect(),
+                progress_file: format!("{}.progress", path),
+            });
+        }
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let campaign = Campaign::parse(&text, dir, &format!("{}.progress", path));
+        if campaign.levels.is_empty() {
+            return Err(format!("There are no levels in {}", path));
+        }
+        Ok(campaign)
+    }
+
+    fn parse(text: &str, dir: &Path, progress_file: &str) -> Campaign {
+        let levels = text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let mut args: Vec<String> = line.split_whitespace().map(|a| a.to_owned()).collect();
+                if !args[0].starts_with('-') {
+                    let file = dir.join(&args[0]).to_string_lossy().into_owned();
+                    args.splice(..1, vec!["--maze".to_owned(), file]);
+                }
+                args
+            })
+            .collect();
+        Campaign {
+            levels,
+            progress_file: progress_file.to_owned(),
+        }
+    }
+
+    /// How many levels have been cleared, or 0 for a new campaign.
+    pub fn progress(&self) -> usize {
+        let mut text = String::new();
+        if File::open(&self.progress_file).and_then(|mut f| f.read_to_string(&mut text)).is_err() {
+            return 0;
+        }
+        match text.lines().next().map(split_header) {
+            Some(Ok(("cleared", cleared))) => cleared.parse().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    pub fn save_progress(&self, cleared: usize) -> ::std::io::Result<()> {
+        let mut f = File::create(&self.progress_file)?;
+        writeln!(f, "cleared: {}", cleared)
+    }
+}
+
+#[test]
+fn test_parse_manifest() {
+    let text = "# Start gently.\n\
+                ambush.txt\n\
+                \n\
+                --generator prim --size 8 --trolls 3\n  \
+                cave.pgm --trolls 2\n";
+    let campaign = Campaign::parse(text, Path::new("levels"), "campaign.progress");
+    let args = |line: &str| line.split(' ').map(|a| a.to_owned()).collect::<Vec<_>>();
+    assert_eq!(campaign.levels,
+               vec![args("--maze levels/ambush.txt"),
+                    args("--generator prim --size 8 --trolls 3"),
+                    args("--maze levels/cave.pgm --trolls 2")]);
+}
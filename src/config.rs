@@ -0,0 +1,161 @@
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use generator::Algorithm;
+use input::Key;
+use keymap::{Command, Preset};
+use level::split_header;
+use screen::{parse_colour, RenderMode, Theme};
+use topology::Shape;
+
+/// Personal settings, read from `$XDG_CONFIG_HOME/maze/config`, or
+/// `~/.config/maze/config` when that isn't set:
+///
+/// ```text
+/// # Defaults, which the command line overrides.
+/// size: 12
+/// trolls: 3
+/// generator: prim
+/// render: colour
+///
+/// # Colours are black, red, green, yellow, blue, purple, cyan, white or a
+/// # number from 0 to 255.
+/// wall-colour: 94
+/// troll-colour: cyan
+///
+/// # Start from the wasd, vi or numpad keys, then change some. Keys are a
+/// # character, or up, down, left, right, home, end, page-up, page-down,
+/// # insert, delete, enter, tab, backspace, esc, space, f1 to f12 or
+/// # ctrl-<letter>.
+/// keys: vi
+/// key: x quit
+/// key: esc quit
+/// key: S none
+/// ```
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Config {
+    /// The defaults as command line options, to go before the real ones.
+    pub args: Vec<String>,
+    pub theme: Theme,
+    /// Changes to the keys, made after picking the preset. `None` unbinds.
+    pub bindings: Vec<(Key, Option<Command>)>,
+}
+
+/// Where the config file is, if there's anywhere to look.
+pub fn path() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+    .map(|dir| dir.join("maze").join("config"))
+}
+
+/// Loads the config file. Not having one is fine, but anything wrong in it
+/// isn't.
+pub fn load() -> Result<Config, String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+    };
+    let mut text = String::new();
+    match File::open(&path).and_then(|mut f| f.read_to_string(&mut text)) {
+        Ok(_) => {}
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    }
+    parse(&text).map_err(|e| format!("{}, {}", path.display(), e))
+}
+
+fn check<T: ::std::str::FromStr>(value: &str) -> Result<(), String>
+    where T::Err: ToString
+{
+    value.parse::<T>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let setting = split_header(line).and_then(|(key, value)| {
+            match key {
+                "size" | "width" | "height" | "trolls" | "braid" | "exits" => {
+                    check::<usize>(value)
+                        .map_err(|_| format!("{} should be a number, not '{}'", key, value))?
+                }
+                "generator" => check::<Algorithm>(value)?,
+                "shape" => check::<Shape>(value)?,
+                "render" => check::<RenderMode>(value)?,
+                "keys" => check::<Preset>(value)?,
+                "key" => {
+                    let (key, command) = value.split_once(' ')
+                        .ok_or_else(|| format!("expected 'key: <key> <command>', got '{}'", line))?;
+                    let command = match command.trim() {
+                        "none" => None,
+                        command => Some(command.parse()?),
+                    };
+                    config.bindings.push((key.parse()?, command));
+                    return Ok(());
+                }
+                _ => {
+                    let theme = &mut config.theme;
+                    let colour = match key {
+                        "wall-colour" => &mut theme.wall,
+                        "exit-colour" => &mut theme.exit,
+                        "stairs-colour" => &mut theme.stairs,
+                        "goal-colour" => &mut theme.goal,
+                        "player-colour" => &mut theme.player,
+                        "troll-colour" => &mut theme.troll,
+                        "dead-troll-colour" => &mut theme.dead_troll,
+                        _ => return Err(format!("unknown setting '{}'", key)),
+                    };
+                    *colour = parse_colour(value)?;
+                    return Ok(());
+                }
+            }
+            config.args.push(format!("--{}", key));
+            config.args.push(value.to_owned());
+            Ok(())
+        });
+        setting.map_err(|e| format!("line {}: {}", i + 1, e))?;
+    }
+    Ok(config)
+}
+
+#[test]
+fn test_parse_config() {
+    use ansi_term::Colour::{Cyan, Fixed};
+    use direction::Direction::North;
+
+    let config = parse("# Mine\n\
+                        size: 12\n\
+                        generator: prim\n\
+                        \n\
+                        wall-colour: 94\n\
+                        troll-colour: cyan\n\
+                        keys: vi\n\
+                        key: k north\n\
+                        key: S none\n\
+                        key: f1 help\n")
+        .unwrap();
+    assert_eq!(config.args, vec!["--size", "12", "--generator", "prim", "--keys", "vi"]);
+    assert_eq!(config.theme.wall, Fixed(94));
+    assert_eq!(config.theme.troll, Cyan);
+    assert_eq!(config.bindings,
+               vec![(Key::Char('k'), Some(Command::Move(North))),
+                    (Key::Char('S'), None),
+                    (Key::F(1), Some(Command::Help))]);
+
+    let error = |text: &str| parse(text).unwrap_err();
+    assert_eq!(error("size: 12\ntrolls: lots\n"),
+               "line 2: trolls should be a number, not 'lots'");
+    assert!(error("generator: magic\n").starts_with("line 1: Unknown generator 'magic'"));
+    assert_eq!(error("colour: red\n"), "line 1: unknown setting 'colour'");
+    assert!(error("key: k\n").contains("expected 'key: <key> <command>'"));
+    assert!(error("goal-colour: mauve\n").contains("Unknown colour 'mauve'"));
+}
@@ -1,5 +1,6 @@
 
-use rand::{Rand, Rng};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Direction {
@@ -7,25 +8,41 @@ pub enum Direction {
     East,
     South,
     West,
+    // Only used by the hex topology.
+    NorthEast,
+    SouthEast,
+    SouthWest,
+    NorthWest,
 }
 pub use self::Direction::*;
 
+const DIRECTIONS: [Direction; 8] =
+    [North, East, South, West, NorthEast, SouthEast, SouthWest, NorthWest];
+
 impl Direction {
-    //                        row, col
-    pub fn numeric(&self) -> (i32, i32) {
+    pub fn name(&self) -> &'static str {
         match *self {
-            North => (-1, 0),
-            South => (1, 0),
-            East => (0, 1),
-            West => (0, -1),
+            North => "north",
+            South => "south",
+            East => "east",
+            West => "west",
+            NorthEast => "northeast",
+            SouthEast => "southeast",
+            SouthWest => "southwest",
+            NorthWest => "northwest",
         }
     }
+
     pub fn unicode(&self) -> &'static str {
         match *self {
             North => "▲",
             South => "▼",
             East => "▶",
             West => "◀",
+            NorthEast => "◥",
+            SouthEast => "◢",
+            SouthWest => "◣",
+            NorthWest => "◤",
         }
     }
 
@@ -35,12 +52,26 @@ impl Direction {
             South => North,
             East => West,
             West => East,
+            NorthEast => SouthWest,
+            SouthWest => NorthEast,
+            SouthEast => NorthWest,
+            NorthWest => SouthEast,
         }
     }
 }
 
-impl Rand for Direction {
-    fn rand<R: Rng>(rng: &mut R) -> Self {
-        *rng.choose(&[North, South, East, West]).unwrap()
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Direction, String> {
+        DIRECTIONS.iter()
+            .find(|d| d.name() == s)
+            .cloned()
+            .ok_or_else(|| format!("Unknown direction '{}'", s))
     }
 }
@@ -0,0 +1,170 @@
+use rand::Rng;
+
+use generator::{Cells, MazeGenerator};
+use posn::Posn;
+
+/// A rectangle of cells, `top_left` inclusive and `bottom_right` exclusive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Room {
+    top_left: Posn,
+    bottom_right: Posn,
+}
+
+impl Room {
+    /// Whether the rooms overlap or touch, which would merge them into one.
+    fn touches(&self, other: &Room) -> bool {
+        self.top_left.row <= other.bottom_right.row &&
+        other.top_left.row <= self.bottom_right.row &&
+        self.top_left.col <= other.bottom_right.col &&
+        other.top_left.col <= self.bottom_right.col
+    }
+
+    fn random_cell<R: Rng>(&self, rng: &mut R) -> Posn {
+        Posn {
+            row: rng.gen_range(self.top_left.row, self.bottom_right.row),
+            col: rng.gen_range(self.top_left.col, self.bottom_right.col),
+        }
+    }
+}
+
+/// Rectangular rooms joined by corridors, with the exit at the end of a
+/// corridor leading out to the border.
+///
+/// Unlike the maze generators this leaves solid rock between the rooms, and
+/// the open rooms give trolls long lines of sight.
+pub struct Dungeon {
+    /// How many times to try placing a room; overlapping ones are dropped.
+    pub attempts: usize,
+    /// The smallest and largest room side, in cells.
+    pub room_size: (usize, usize),
+}
+
+impl Default for Dungeon {
+    fn default() -> Dungeon {
+        Dungeon {
+            attempts: 30,
+            room_size: (2, 5),
+        }
+    }
+}
+
+impl Dungeon {
+    fn place_rooms<R: Rng>(&self, cells: &Cells, rng: &mut R) -> Vec<Room> {
+        let (min_size, max_size) = self.room_size;
+        let mut rooms: Vec<Room> = vec![];
+        for _ in 0..self.attempts {
+            let height = rng.gen_range(min_size, max_size + 1).min(cells.height);
+            let width = rng.gen_range(min_size, max_size + 1).min(cells.width);
+            let top_left = Posn {
+                row: rng.gen_range(0, (cells.height - height + 1) as i32),
+                col: rng.gen_range(0, (cells.width - width + 1) as i32),
+            };
+            let room = Room {
+                top_left,
+                bottom_right: top_left + (height as i32, width as i32),
+            };
+            if !rooms.iter().any(|r| r.touches(&room)) {
+                rooms.push(room);
+            }
+        }
+        rooms
+    }
+
+    /// Carves an L-shaped corridor, picking at random which leg goes first.
+    fn corridor<R: Rng>(cells: &mut Cells, from: Posn, to: Posn, rng: &mut R) {
+        let corner = if rng.gen() {
+            Posn {
+                row: from.row,
+                col: to.col,
+            }
+        } else {
+            Posn {
+                row: to.row,
+                col: from.col,
+            }
+        };
+        for &(start, end) in &[(from, corner), (corner, to)] {
+            let mut curr = start;
+            cells.open(curr);
+            while curr != end {
+                // Each leg runs along a row or a column, and those steps are
+                // next door on a hex grid too.
+                let next = curr + ((end.row - curr.row).signum(), (end.col - curr.col).signum());
+                cells.connect(curr, next);
+                curr = next;
+            }
+        }
+    }
+}
+
+impl MazeGenerator for Dungeon {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        let rooms = self.place_rooms(cells, rng);
+        info!("Placed {} rooms", rooms.len());
+        for room in &rooms {
+            cells.open_rect(room.top_left, room.bottom_right + (-1, -1));
+        }
+        // Chaining each room to the one placed before it joins them all up.
+        for pair in rooms.windows(2) {
+            let (from, to) = (pair[0].random_cell(rng), pair[1].random_cell(rng));
+            Dungeon::corridor(cells, from, to, rng);
+        }
+
+        // Run a corridor straight out from one room to the border.
+        let start = rng.choose(&rooms).unwrap().random_cell(rng);
+        let topology = cells.shape.topology();
+        let dir = *rng.choose(topology.directions()).unwrap();
+        let (rows, cols) = topology.offset(dir).unwrap();
+        let edge = if rows < 0 {
+            Posn { row: 0, ..start }
+        } else if rows > 0 {
+            Posn { row: cells.height as i32 - 1, ..start }
+        } else if cols < 0 {
+            Posn { col: 0, ..start }
+        } else {
+            Posn { col: cells.width as i32 - 1, ..start }
+        };
+        Dungeon::corridor(cells, start, edge, rng);
+        cells.open_exit(edge, dir);
+    }
+}
+
+#[test]
+fn test_dungeon_is_connected() {
+    use std::collections::HashSet;
+    use rng::GameRng;
+    use topology::Shape;
+    use tile::Tile;
+
+    for seed in 0..20 {
+        let mut rng = GameRng::new(seed);
+        let maze = Dungeon::default().generate(Shape::Square, 15, 25, false, &mut rng);
+        let (rows, cols) = maze.bounds();
+
+        let mut exits = vec![];
+        let mut floors = 0;
+        for row in 0..rows as i32 {
+            for col in 0..cols as i32 {
+                match maze[&Posn { row, col }] {
+                    Tile::Exit => exits.push(Posn { row, col }),
+                    Tile::Floor => floors += 1,
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(exits.len(), 1);
+        let exit = exits[0];
+        assert!(exit.row == 0 || exit.col == 0 || exit.row == rows as i32 - 1 ||
+                exit.col == cols as i32 - 1);
+
+        let mut seen = HashSet::new();
+        let mut todo = vec![exit];
+        while let Some(p) = todo.pop() {
+            if maze[&p] == Tile::Wall || !seen.insert(p) {
+                continue;
+            }
+            todo.extend(maze.topology().neighbours(p));
+        }
+        assert_eq!(seen.len(), floors + 1, "seed {}", seed);
+    }
+}
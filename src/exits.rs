@@ -0,0 +1,207 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use maze::Maze;
+use pathfind::distances;
+use posn::Posn;
+use tile::Tile;
+
+/// Where to put the exits on the border of a maze.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitPlacement {
+    /// As close to the corners as possible, starting at the top left.
+    Corner,
+    /// Anywhere along the border.
+    RandomBorder,
+    /// As far as possible from where the player starts.
+    Farthest,
+}
+
+const PLACEMENTS: [ExitPlacement; 3] =
+    [ExitPlacement::Corner, ExitPlacement::RandomBorder, ExitPlacement::Farthest];
+
+impl ExitPlacement {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ExitPlacement::Corner => "corner",
+            ExitPlacement::RandomBorder => "random",
+            ExitPlacement::Farthest => "farthest",
+        }
+    }
+}
+
+impl fmt::Display for ExitPlacement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ExitPlacement {
+    type Err = String;
+    fn from_str(s: &str) -> Result<ExitPlacement, String> {
+        PLACEMENTS.iter()
+            .find(|p| p.name() == s)
+            .cloned()
+            .ok_or_else(|| {
+                format!("Unknown exit placement '{}', expected corner, random or farthest", s)
+            })
+    }
+}
+
+/// Border walls with a floor tile just inside them, where an exit could go.
+fn candidates(maze: &Maze) -> Vec<Posn> {
+    let (rows, cols) = maze.bounds();
+    let mut candidates = vec![];
+    for row in 0..rows as i32 {
+        for col in 0..cols as i32 {
+            let pos = Posn { row, col };
+            if maze.on_border(pos) && maze[&pos] == Tile::Wall &&
+               maze.neighbours(pos)
+                   .into_iter()
+                   .any(|p| maze.in_bounds(&p) && maze[&p] == Tile::Floor) {
+                candidates.push(pos);
+            }
+        }
+    }
+    candidates
+}
+
+/// Picks a floor tile for the player that isn't close to any exit.
+///
+/// Tiles closer than a quarter of the way to the farthest reachable tile
+/// are ruled out, so the player never starts right next to an exit.
+pub fn spawn_point<R: Rng>(maze: &Maze, rng: &mut R) -> Posn {
+    let exits = maze.exits();
+    let dist = distances(maze, &exits);
+    let mut floors = vec![];
+    let mut farthest = 0;
+    for (row, line) in dist.0.iter().enumerate() {
+        for (col, d) in line.iter().enumerate() {
+            let pos = Posn {
+                row: row as i32,
+                col: col as i32,
+            };
+            if maze[&pos] == Tile::Floor {
+                floors.push((pos, *d));
+                farthest = farthest.max(d.unwrap_or(0));
+            }
+        }
+    }
+    let threshold = (farthest / 4).max(2);
+    let far_enough: Vec<Posn> = floors.iter()
+        .filter(|&&(_, d)| d.is_some_and(|d| d >= threshold))
+        .map(|&(pos, _)| pos)
+        .collect();
+    match rng.choose(&far_enough) {
+        Some(&pos) => pos,
+        // A tiny maze, or no exits at all. Anywhere will have to do.
+        None => maze.random_floor_tile(rng),
+    }
+}
+
+/// Replaces the exits in a maze with `count` new ones, and returns where
+/// the player should start.
+pub fn place_exits<R: Rng>(maze: &mut Maze,
+                           placement: ExitPlacement,
+                           count: usize,
+                           rng: &mut R)
+                           -> Posn {
+    for exit in maze.exits() {
+        maze.set(&exit, Tile::Wall);
+    }
+    let mut candidates = candidates(maze);
+    let count = count.min(candidates.len());
+    info!("Placing {} exits by {:?}", count, placement);
+
+    let mut start = None;
+    let exits: Vec<Posn> = match placement {
+        ExitPlacement::Corner => {
+            let (rows, cols) = maze.bounds();
+            let (rows, cols) = (rows as i32 - 1, cols as i32 - 1);
+            let corners = [Posn { row: 0, col: 0 },
+                           Posn {
+                               row: rows,
+                               col: cols,
+                           },
+                           Posn { row: 0, col: cols },
+                           Posn { row: rows, col: 0 }];
+            let mut exits = vec![];
+            for corner in corners.iter().cycle().take(count) {
+                // Ties go to the lower column, so the classic exit stays put.
+                let (i, _) = candidates.iter()
+                    .enumerate()
+                    .min_by_key(|&(_, p)| {
+                        ((p.row - corner.row).abs() + (p.col - corner.col).abs(), p.col, p.row)
+                    })
+                    .unwrap();
+                exits.push(candidates.remove(i));
+            }
+            exits
+        }
+        ExitPlacement::RandomBorder => {
+            rng.shuffle(&mut candidates);
+            candidates.truncate(count);
+            candidates
+        }
+        ExitPlacement::Farthest => {
+            let from = maze.random_floor_tile(rng);
+            start = Some(from);
+            let dist = distances(maze, &[from]);
+            let inside = |p: &Posn| -> Option<usize> {
+                maze.neighbours(*p)
+                    .into_iter()
+                    .filter(|a| maze.in_bounds(a))
+                    .filter_map(|a| dist[a])
+                    .min()
+            };
+            let mut reachable: Vec<(usize, Posn)> =
+                candidates.iter().filter_map(|p| inside(p).map(|d| (d, *p))).collect();
+            reachable.sort_by(|a, b| b.cmp(a));
+            reachable.into_iter().take(count).map(|(_, p)| p).collect()
+        }
+    };
+    for exit in exits {
+        maze.set(&exit, Tile::Exit);
+    }
+    match start {
+        Some(start) => start,
+        None => spawn_point(maze, rng),
+    }
+}
+
+#[test]
+fn test_place_exits() {
+    use generator::Algorithm;
+    use topology::Shape;
+    use rng::GameRng;
+
+    let mut rng = GameRng::new(42);
+    let base = Algorithm::RecursiveBacktracker.generate(Shape::Square, 10, 10, false, &mut rng);
+
+    let mut maze = base.clone();
+    place_exits(&mut maze, ExitPlacement::Corner, 1, &mut rng);
+    assert_eq!(maze, base);
+
+    for &placement in &PLACEMENTS {
+        let mut maze = base.clone();
+        let start = place_exits(&mut maze, placement, 3, &mut rng);
+        let exits = maze.exits();
+        assert_eq!(exits.len(), 3);
+        assert_eq!(maze[&start], Tile::Floor);
+        let dist = distances(&maze, &exits);
+        assert!(dist[start].unwrap() >= 2, "{} started next to an exit", placement);
+    }
+
+    let mut maze = base.clone();
+    let start = place_exits(&mut maze, ExitPlacement::Farthest, 1, &mut rng);
+    let dist = distances(&maze, &[start]);
+    let exit_dist = dist[maze.exits()[0]].unwrap();
+    for candidate in candidates(&maze) {
+        let next_door = maze.topology().neighbours(candidate);
+        for p in next_door.filter(|p| maze.in_bounds(p) && maze[p] == Tile::Floor) {
+            assert!(dist[p].is_none_or(|d| d < exit_dist));
+        }
+    }
+}
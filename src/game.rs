@@ -0,0 +1,343 @@
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+
+use level::{parse_wrap, split_header, Metadata};
+use maze::{LoadError, Maze, MazeParseError, ParseErrorKind};
+use player::Player;
+use posn::Posn;
+use rng::GameRng;
+use tile::Tile;
+use topology::Shape;
+use tower::Tower;
+use troll::Troll;
+
+const MAGIC: &str = "maze-save";
+const VERSION: u32 = 1;
+
+/// Everything needed to carry on a game exactly where it left off.
+///
+/// Save files start with a header of `key: value` lines, one `troll` line
+/// per troll, then each floor's map after a `floor <n>` line:
+///
+/// ```text
+/// maze-save 1
+/// seed: 42
+/// rng: 1234567890
+/// ticks: 17
+/// shape: square
+/// wrap: no
+/// floor: 1
+/// player: 3 5 east
+/// troll: 1 3 7 north alive wandering
+///
+/// floor 1
+/// #####
+/// #   X
+/// #####
+/// ```
+///
+/// Floors count from 1, rows and columns from 0. A saved level also keeps
+/// its name, author, par and ruleset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameState {
+    /// What the game started with, to show at the end.
+    pub seed: u64,
+    pub rng: GameRng,
+    pub ticks: u64,
+    pub tower: Tower,
+    /// Which floor the player is on.
+    pub floor: usize,
+    pub player: Player,
+    pub metadata: Option<Metadata>,
+}
+
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ground = &self.tower.floors[0];
+        writeln!(f, "{} {}", MAGIC, VERSION)?;
+        writeln!(f, "seed: {}", self.seed)?;
+        writeln!(f, "rng: {}", self.rng.state())?;
+        writeln!(f, "ticks: {}", self.ticks)?;
+        writeln!(f, "shape: {}", ground.shape)?;
+        writeln!(f, "wrap: {}", if ground.wrap { "yes" } else { "no" })?;
+        writeln!(f, "floor: {}", self.floor + 1)?;
+        writeln!(f,
+                 "player: {} {} {}",
+                 self.player.pos.row,
+                 self.player.pos.col,
+                 self.player.dir)?;
+        if let Some(ref metadata) = self.metadata {
+            write!(f, "{}", metadata)?;
+        }
+        for (floor, maze) in self.tower.floors.iter().enumerate() {
+            for (pos, troll) in &maze.trolls {
+                writeln!(f,
+                         "troll: {} {} {} {} {} {}",
+                         floor + 1,
+                         pos.row,
+                         pos.col,
+                         troll.dir,
+                         if troll.alive { "alive" } else { "dead" },
+                         troll.state)?;
+            }
+        }
+        for (floor, maze) in self.tower.floors.iter().enumerate() {
+            writeln!(f, "\nfloor {}", floor + 1)?;
+            for row in 0..maze.bounds().0 {
+                writeln!(f, "{}", maze.text_row(row))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads `<row> <col>` from the front of `words`.
+fn parse_posn(words: &[&str]) -> Result<Posn, String> {
+    match *words {
+        [row, col, ..] => {
+            Ok(Posn {
+                row: row.parse().map_err(|_| format!("bad row '{}'", row))?,
+                col: col.parse().map_err(|_| format!("bad column '{}'", col))?,
+            })
+        }
+        _ => Err("expected a row and a column".to_owned()),
+    }
+}
+
+/// Reads `<floor> <row> <col> <facing> alive|dead <state>`.
+fn parse_troll(value: &str) -> Result<(usize, Posn, Troll), String> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    if words.len() < 6 {
+        return Err(format!("expected floor, row, column, facing, alive and state, got '{}'",
+                           value));
+    }
+    let floor: usize = words[0].parse().map_err(|_| format!("bad floor '{}'", words[0]))?;
+    let mut troll = Troll::new(words[3].parse()?);
+    troll.alive = match words[4] {
+        "alive" => true,
+        "dead" => false,
+        other => return Err(format!("expected alive or dead, got '{}'", other)),
+    };
+    troll.state = words[5..].join(" ").parse()?;
+    Ok((floor, parse_posn(&words[1..])?, troll))
+}
+
+impl GameState {
+    pub fn save(&self, filename: &str) -> ::std::io::Result<()> {
+        let mut f = File::create(filename)?;
+        write!(f, "{}", self)
+    }
+
+    pub fn from_file(filename: &str) -> Result<GameState, LoadError> {
+        let mut text = String::new();
+        File::open(filename)?.read_to_string(&mut text)?;
+        Ok(GameState::parse(&text)?)
+    }
+
+    pub fn parse(text: &str) -> Result<GameState, MazeParseError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let header_error = |i: usize, reason: String| {
+            Err(MazeParseError {
+                line: i + 1,
+                column: 1,
+                kind: ParseErrorKind::BadHeader(reason),
+            })
+        };
+
+        let version = lines.first().and_then(|l| l.strip_prefix(MAGIC)).map(|v| v.trim());
+        match version {
+            Some(version) if version.parse() == Ok(VERSION) => {}
+            Some(version) => {
+                return Err(MazeParseError {
+                    line: 1,
+                    column: MAGIC.len() + 2,
+                    kind: ParseErrorKind::UnknownVersion(version.to_owned()),
+                })
+            }
+            None => return header_error(0, format!("expected '{} {}'", MAGIC, VERSION)),
+        }
+
+        let (mut seed, mut rng, mut ticks) = (None, None, None);
+        let (mut shape, mut wrap) = (Shape::Square, false);
+        let (mut floor, mut player) = (None, None);
+        let mut metadata = Metadata::default();
+        let mut has_metadata = false;
+        let mut trolls = vec![];
+        let mut i = 1;
+        while i < lines.len() && !lines[i].is_empty() {
+            let header = split_header(lines[i]).and_then(|(key, value)| {
+                let number = || {
+                    value.parse::<u64>().map_err(|_| format!("bad {} '{}'", key, value))
+                };
+                match key {
+                    "seed" => seed = Some(number()?),
+                    "rng" => rng = Some(GameRng::new(number()?)),
+                    "ticks" => ticks = Some(number()?),
+                    "shape" => shape = value.parse()?,
+                    "wrap" => wrap = parse_wrap(value)?,
+                    "floor" => floor = Some(number()?),
+                    "player" => {
+                        let words: Vec<&str> = value.split_whitespace().collect();
+                        let dir = words.get(2).ok_or("the player needs a facing")?.parse()?;
+                        player = Some(Player {
+                            pos: parse_posn(&words)?,
+                            dir,
+                        });
+                    }
+                    "troll" => trolls.push((i, parse_troll(value)?)),
+                    _ => {
+                        if !metadata.set(key, value)? {
+                            return Err(format!("unknown key '{}'", key));
+                        }
+                        has_metadata = true;
+                    }
+                }
+                Ok(())
+            });
+            if let Err(reason) = header {
+                return header_error(i, reason);
+            }
+            i += 1;
+        }
+
+        let mut floors: Vec<Maze> = vec![];
+        while i < lines.len() {
+            // Skip the blank line before each floor.
+            i += 1;
+            if i >= lines.len() {
+                break;
+            }
+            if lines[i] != format!("floor {}", floors.len() + 1) {
+                return header_error(i, format!("expected 'floor {}'", floors.len() + 1));
+            }
+            let start = i + 1;
+            let mut end = start;
+            while end < lines.len() && !lines[end].is_empty() {
+                end += 1;
+            }
+            let maze = Maze::parse_rows(&lines[start..end], start + 1, shape, wrap, |c, _| {
+                    match c {
+                        '<' => Some(Tile::UpStairs),
+                        '>' => Some(Tile::DownStairs),
+                        _ => None,
+                    }
+                })?;
+            if floors.first().is_some_and(|first| first.bounds() != maze.bounds()) {
+                return header_error(i, "every floor has to be the same size".to_owned());
+            }
+            floors.push(maze);
+            i = end;
+        }
+        if floors.is_empty() {
+            return header_error(lines.len(), "there are no floors".to_owned());
+        }
+        floors.last().unwrap().check_exits(lines.len())?;
+        let mut tower = Tower { floors };
+
+        let missing = |key: &str| header_error(0, format!("missing '{}'", key));
+        let floor = match floor {
+            Some(floor) if floor >= 1 && floor as usize <= tower.floors.len() => floor as usize - 1,
+            Some(floor) => return header_error(0, format!("there's no floor {}", floor)),
+            None => return missing("floor"),
+        };
+        for (line, (troll_floor, pos, troll)) in trolls {
+            if troll_floor < 1 || troll_floor > tower.floors.len() ||
+               !tower.floors[troll_floor - 1].in_bounds(&pos) {
+                return header_error(line, "troll is off the map".to_owned());
+            }
+            tower.floors[troll_floor - 1].add_troll(pos, troll);
+        }
+        let player = match player {
+            Some(player) => player,
+            None => return missing("player"),
+        };
+        if !tower.floors[floor].in_bounds(&player.pos) {
+            return header_error(0, "the player is off the map".to_owned());
+        }
+
+        Ok(GameState {
+            seed: match seed {
+                Some(seed) => seed,
+                None => return missing("seed"),
+            },
+            rng: match rng {
+                Some(rng) => rng,
+                None => return missing("rng"),
+            },
+            ticks: match ticks {
+                Some(ticks) => ticks,
+                None => return missing("ticks"),
+            },
+            tower,
+            floor,
+            player,
+            metadata: if has_metadata { Some(metadata) } else { None },
+        })
+    }
+}
+
+#[test]
+fn test_save_round_trip() {
+    use direction::Direction;
+    use generator::Algorithm;
+    use rand::Rng;
+    use troll::State;
+
+    let mut rng = GameRng::new(77);
+    let floors = (0..2)
+        .map(|_| Algorithm::Kruskal.generate(Shape::Hex, 5, 6, false, &mut rng))
+        .collect();
+    let mut tower = Tower::stack(floors, &mut rng).unwrap();
+    for (i, maze) in tower.floors.iter_mut().enumerate() {
+        let pos = maze.random_floor_tile(&mut rng);
+        let mut troll = Troll::new(Direction::SouthWest);
+        troll.alive = i == 0;
+        troll.state = State::Stunned(2);
+        maze.add_troll(pos, troll);
+    }
+    let player = Player {
+        pos: tower.floors[1].random_floor_tile(&mut rng),
+        dir: Direction::NorthEast,
+    };
+    rng.next_u64();
+    let state = GameState {
+        seed: 77,
+        rng,
+        ticks: 123,
+        tower,
+        floor: 1,
+        player,
+        metadata: Some(Metadata {
+            par: Some(99),
+            ..Metadata::default()
+        }),
+    };
+
+    let text = state.to_string();
+    assert_eq!(GameState::parse(&text), Ok(state.clone()));
+
+    let error = GameState::parse(&text.replace("floor: 2", "floor: 3")).unwrap_err();
+    assert_eq!(error.line, 1);
+    assert!(GameState::parse(&text.replace("ticks: 123\n", "")).is_err());
+
+    // Passages cross the seam of a wrapping maze, where the outer wall
+    // would be.
+    let maze = Algorithm::RecursiveBacktracker.generate(Shape::Square, 10, 10, true, &mut rng);
+    let player = Player {
+        pos: maze.random_floor_tile(&mut rng),
+        dir: Direction::East,
+    };
+    let wrapped = GameState {
+        tower: Tower::stack(vec![maze], &mut rng).unwrap(),
+        floor: 0,
+        player,
+        metadata: None,
+        ..state
+    };
+    let maze = &wrapped.tower.floors[0];
+    let (rows, cols) = maze.bounds();
+    assert!((0..cols).any(|col| maze[(0, col)] == Tile::Floor) ||
+            (0..rows).any(|row| maze[(row, 0)] == Tile::Floor));
+    assert_eq!(GameState::parse(&wrapped.to_string()), Ok(wrapped));
+}
@@ -0,0 +1,684 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use direction::Direction;
+use dungeon::Dungeon;
+use maze::Maze;
+use packed::TileMap;
+use posn::Posn;
+use tile::Tile;
+use topology::Shape;
+
+/// The carving surface shared by every generator.
+///
+/// Generators work in cell coordinates: cell `(row, col)` lives at
+/// `(2 * row + 1, 2 * col + 1)` in the tile map, and the tile between two
+/// neighbouring cells is the wall that gets knocked out to join them. That
+/// holds for any topology whose steps are fixed offsets, hexes included.
+///
+/// When the cells wrap, the last row and column are neighbours of the first,
+/// and the top and left walls are the seam between them. There's no bottom
+/// or right wall, so the map is one tile smaller each way.
+pub struct Cells {
+    map: TileMap,
+    pub height: usize,
+    pub width: usize,
+    pub shape: Shape,
+    pub wrap: bool,
+}
+
+impl Cells {
+    pub fn new(height: usize, width: usize, shape: Shape, wrap: bool) -> Cells {
+        let border = if wrap { 0 } else { 1 };
+        Cells {
+            map: TileMap::new(height * 2 + border, width * 2 + border, Tile::Wall),
+            height,
+            width,
+            shape,
+            wrap,
+        }
+    }
+
+    pub fn contains(&self, p: Posn) -> bool {
+        p.inside(Posn { row: 0, col: 0 },
+                 Posn {
+                     row: self.height as i32,
+                     col: self.width as i32,
+                 })
+    }
+
+    pub fn is_open(&self, p: Posn) -> bool {
+        let c = centre(p);
+        *self.map.get(c.row as usize, c.col as usize) == Tile::Floor
+    }
+
+    pub fn open(&mut self, p: Posn) {
+        let c = centre(p);
+        self.map.set(c.row as usize, c.col as usize, Tile::Floor);
+    }
+
+    /// Opens both cells and the wall between them.
+    pub fn connect(&mut self, a: Posn, b: Posn) {
+        let wall = self.wall_between(a, b);
+        self.open(a);
+        self.open(b);
+        self.map.set(wall.row as usize, wall.col as usize, Tile::Floor);
+    }
+
+    /// Opens every tile from cell `a` to cell `b` inclusive, including the
+    /// wall corners between cells, leaving one open room.
+    pub fn open_rect(&mut self, a: Posn, b: Posn) {
+        let (a, b) = (centre(a), centre(b));
+        for row in a.row.min(b.row)..a.row.max(b.row) + 1 {
+            for col in a.col.min(b.col)..a.col.max(b.col) + 1 {
+                self.map.set(row as usize, col as usize, Tile::Floor);
+            }
+        }
+    }
+
+    pub fn neighbours(&self, p: Posn) -> Vec<Posn> {
+        let neighbours = self.shape.topology().neighbours(p);
+        if self.wrap {
+            let (height, width) = (self.height as i32, self.width as i32);
+            neighbours.map(|n| {
+                    Posn {
+                        row: n.row.rem_euclid(height),
+                        col: n.col.rem_euclid(width),
+                    }
+                })
+                // A single row or column wraps onto itself.
+                .filter(|&n| n != p)
+                .collect()
+        } else {
+            neighbours.filter(|&n| self.contains(n)).collect()
+        }
+    }
+
+    /// The wall tile between two neighbouring cells, which may be on
+    /// opposite sides of the seam.
+    fn wall_between(&self, a: Posn, b: Posn) -> Posn {
+        let (mut rows, mut cols) = (b.row - a.row, b.col - a.col);
+        if self.wrap {
+            // Neighbours across the seam look a whole maze apart.
+            let across = |d: i32, size: usize| if d > 1 {
+                d - size as i32
+            } else if d < -1 {
+                d + size as i32
+            } else {
+                d
+            };
+            rows = across(rows, self.height);
+            cols = across(cols, self.width);
+        }
+        self.tile(centre(a) + (rows, cols))
+    }
+
+    /// Brings a tile position back onto the map across the seam.
+    fn tile(&self, p: Posn) -> Posn {
+        if !self.wrap {
+            return p;
+        }
+        let (rows, cols) = (self.map.rows() as i32, self.map.cols() as i32);
+        Posn {
+            row: p.row.rem_euclid(rows),
+            col: p.col.rem_euclid(cols),
+        }
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.height * self.width
+    }
+
+    pub fn random_cell<R: Rng>(&self, rng: &mut R) -> Posn {
+        Posn {
+            row: rng.gen_range(0, self.height as i32),
+            col: rng.gen_range(0, self.width as i32),
+        }
+    }
+
+    /// Turns the border wall on the `dir` side of an edge cell into the exit.
+    pub fn open_exit(&mut self, cell: Posn, dir: Direction) {
+        let exit = self.tile(self.shape.topology().step(centre(cell), dir));
+        let (rows, cols) = (self.map.rows() as i32, self.map.cols() as i32);
+        assert!(exit.row == 0 || exit.col == 0 || exit.row == rows - 1 || exit.col == cols - 1,
+                "Exit {:?} isn't on the border",
+                exit);
+        self.map.set(exit.row as usize, exit.col as usize, Tile::Exit);
+    }
+
+    /// Whether the wall on the `dir` side of `cell` has been knocked out.
+    pub fn is_passage(&self, cell: Posn, dir: Direction) -> bool {
+        let wall = self.tile(self.shape.topology().step(centre(cell), dir));
+        *self.map.get(wall.row as usize, wall.col as usize) != Tile::Wall
+    }
+
+    pub fn has_exit(&self) -> bool {
+        self.map.iter().any(|(_, t)| t == Tile::Exit)
+    }
+
+    pub fn into_map(self) -> TileMap {
+        self.map
+    }
+}
+
+fn centre(p: Posn) -> Posn {
+    Posn {
+        row: 2 * p.row + 1,
+        col: 2 * p.col + 1,
+    }
+}
+
+pub trait MazeGenerator {
+    /// Carves passages into a grid that starts out entirely walled.
+    ///
+    /// Every open cell must be reachable when this returns. Unless the
+    /// generator opens an exit itself, one is put west of the top-left cell,
+    /// so that cell must be open.
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R);
+
+    fn generate<R: Rng>(&self,
+                        shape: Shape,
+                        height: usize,
+                        width: usize,
+                        wrap: bool,
+                        rng: &mut R)
+                        -> Maze {
+        assert!(height > 0 && width > 0);
+        let mut cells = Cells::new(height, width, shape, wrap);
+        self.carve(&mut cells, rng);
+        if !cells.has_exit() {
+            // West of the top-left cell, unless that's a passage across the
+            // seam; then the next solid bit of the seam. Straight up is north
+            // on a square grid, but northwest on a hex one.
+            let topology = shape.topology();
+            let (west, up) = (topology.direction((0, -1)).unwrap(),
+                              topology.direction((-1, 0)).unwrap());
+            let left = (0..height as i32).map(|row| (Posn { row, col: 0 }, west));
+            let top = (0..width as i32).map(|col| (Posn { row: 0, col }, up));
+            let (cell, dir) = left.chain(top)
+                .find(|&(cell, dir)| !cells.is_passage(cell, dir))
+                .unwrap_or((Posn { row: 0, col: 0 }, west));
+            cells.open_exit(cell, dir);
+        }
+        let mut maze = Maze::from_map(cells.into_map());
+        maze.shape = shape;
+        maze.wrap = wrap;
+        maze
+    }
+}
+
+/// Walks randomly until stuck, then scans for an unvisited cell next to the
+/// carved region and starts a new walk from there.
+pub struct HuntAndKill;
+
+impl MazeGenerator for HuntAndKill {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        fn hunt(cells: &Cells, finished_row: usize) -> Option<Posn> {
+            for row in finished_row..cells.height {
+                for col in 0..cells.width {
+                    let p = Posn {
+                        row: row as i32,
+                        col: col as i32,
+                    };
+                    if !cells.is_open(p) {
+                        return Some(p);
+                    }
+                }
+            }
+            None
+        }
+
+        let mut finished_row: usize = 0;
+        cells.open(Posn { row: 0, col: 0 });
+        info!("Generating map");
+        while let Some(mut curr) = hunt(cells, finished_row) {
+            info!("Hunt started new section at {:?}", curr);
+            finished_row = curr.row as usize;
+            let ads: Vec<Posn> = cells.neighbours(curr)
+                .into_iter()
+                .filter(|&p| cells.is_open(p))
+                .collect();
+            let &starting_from = rng.choose(&ads).unwrap();
+            cells.connect(curr, starting_from);
+
+            loop {
+                cells.open(curr);
+                let ads: Vec<Posn> = cells.neighbours(curr)
+                    .into_iter()
+                    .filter(|&p| !cells.is_open(p))
+                    .collect();
+                if let Some(&next) = rng.choose(&ads) {
+                    cells.connect(curr, next);
+                    curr = next;
+                } else {
+                    info!("Exhausted possibilities at {:?}", curr);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Depth-first search with an explicit stack, backing up on dead ends.
+pub struct RecursiveBacktracker;
+
+impl MazeGenerator for RecursiveBacktracker {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        let start = cells.random_cell(rng);
+        cells.open(start);
+        let mut stack = vec![start];
+        while let Some(&curr) = stack.last() {
+            let ads: Vec<Posn> = cells.neighbours(curr)
+                .into_iter()
+                .filter(|&p| !cells.is_open(p))
+                .collect();
+            if let Some(&next) = rng.choose(&ads) {
+                cells.connect(curr, next);
+                stack.push(next);
+            } else {
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// Randomized Prim's: grows the maze from a random frontier cell each step.
+pub struct Prim;
+
+impl MazeGenerator for Prim {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        let start = cells.random_cell(rng);
+        cells.open(start);
+        let mut frontier = cells.neighbours(start);
+        while !frontier.is_empty() {
+            let i = rng.gen_range(0, frontier.len());
+            let curr = frontier.swap_remove(i);
+            if cells.is_open(curr) {
+                // Added twice, and already joined through the other copy.
+                continue;
+            }
+            let (open, closed): (Vec<Posn>, Vec<Posn>) =
+                cells.neighbours(curr).into_iter().partition(|&p| cells.is_open(p));
+            let &from = rng.choose(&open).unwrap();
+            cells.connect(from, curr);
+            frontier.extend(closed);
+        }
+    }
+}
+
+/// Randomized Kruskal's: knocks out walls in random order whenever they
+/// separate two cells that aren't yet connected.
+pub struct Kruskal;
+
+impl MazeGenerator for Kruskal {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        fn find(sets: &mut [usize], i: usize) -> usize {
+            let mut root = i;
+            while sets[root] != root {
+                root = sets[root];
+            }
+            let mut i = i;
+            while sets[i] != root {
+                let next = sets[i];
+                sets[i] = root;
+                i = next;
+            }
+            root
+        }
+
+        let width = cells.width;
+        let index = |p: Posn| p.row as usize * width + p.col as usize;
+        let mut sets: Vec<usize> = (0..cells.cell_count()).collect();
+        let mut edges = vec![];
+        for row in 0..cells.height as i32 {
+            for col in 0..cells.width as i32 {
+                let p = Posn { row, col };
+                cells.open(p);
+                // Only the later neighbours, so each wall is listed once.
+                for next in cells.neighbours(p) {
+                    if next > p {
+                        edges.push((p, next));
+                    }
+                }
+            }
+        }
+        rng.shuffle(&mut edges);
+        for (a, b) in edges {
+            let (set_a, set_b) = (find(&mut sets, index(a)), find(&mut sets, index(b)));
+            if set_a != set_b {
+                sets[set_a] = set_b;
+                cells.connect(a, b);
+            }
+        }
+    }
+}
+
+/// Wilson's algorithm: loop-erased random walks, giving an unbiased sample
+/// of all possible perfect mazes.
+pub struct Wilson;
+
+impl MazeGenerator for Wilson {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        let start = cells.random_cell(rng);
+        cells.open(start);
+        for row in 0..cells.height as i32 {
+            for col in 0..cells.width as i32 {
+                let walk_start = Posn { row, col };
+                // Remembering only the last exit from each cell erases loops.
+                let mut exits = HashMap::new();
+                let mut curr = walk_start;
+                while !cells.is_open(curr) {
+                    let ads = cells.neighbours(curr);
+                    let &next = rng.choose(&ads).unwrap();
+                    exits.insert(curr, next);
+                    curr = next;
+                }
+                let mut path = vec![walk_start];
+                let mut curr = walk_start;
+                while !cells.is_open(curr) {
+                    curr = exits[&curr];
+                    path.push(curr);
+                }
+                for step in path.windows(2) {
+                    cells.connect(step[0], step[1]);
+                }
+            }
+        }
+    }
+}
+
+/// Eller's algorithm: builds the maze one row at a time, tracking which
+/// cells of the current row are already connected.
+pub struct Eller;
+
+impl MazeGenerator for Eller {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        let mut sets: Vec<Option<usize>> = vec![None; cells.width];
+        let mut next_set = 0;
+        for row in 0..cells.height as i32 {
+            let last_row = row as usize == cells.height - 1;
+            for set in sets.iter_mut().filter(|s| s.is_none()) {
+                *set = Some(next_set);
+                next_set += 1;
+            }
+            for col in 0..cells.width as i32 {
+                cells.open(Posn { row, col });
+            }
+
+            for col in 1..cells.width {
+                let (left, right) = (sets[col - 1], sets[col]);
+                if left != right && (last_row || rng.gen()) {
+                    for set in sets.iter_mut().filter(|s| **s == right) {
+                        *set = left;
+                    }
+                    cells.connect(Posn {
+                                      row,
+                                      col: col as i32 - 1,
+                                  },
+                                  Posn {
+                                      row,
+                                      col: col as i32,
+                                  });
+                }
+            }
+            if last_row {
+                break;
+            }
+
+            // Every set needs at least one way down, or it would be cut off.
+            let mut next_sets = vec![None; cells.width];
+            let mut cols: Vec<usize> = (0..cells.width).collect();
+            rng.shuffle(&mut cols);
+            let mut extended = vec![];
+            for &col in &cols {
+                if !extended.contains(&sets[col]) || rng.gen_weighted_bool(3) {
+                    extended.push(sets[col]);
+                    next_sets[col] = sets[col];
+                    cells.connect(Posn {
+                                      row,
+                                      col: col as i32,
+                                  },
+                                  Posn {
+                                      row: row + 1,
+                                      col: col as i32,
+                                  });
+                }
+            }
+            sets = next_sets;
+        }
+    }
+}
+
+/// Growing tree: keeps a list of active cells and extends from either the
+/// newest one (like the backtracker) or a random one (like Prim's).
+pub struct GrowingTree {
+    /// Chance of extending from the newest cell rather than a random one.
+    pub newest: f64,
+}
+
+impl Default for GrowingTree {
+    fn default() -> GrowingTree {
+        GrowingTree { newest: 0.5 }
+    }
+}
+
+impl MazeGenerator for GrowingTree {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        let start = cells.random_cell(rng);
+        cells.open(start);
+        let mut active = vec![start];
+        while !active.is_empty() {
+            let i = if rng.gen::<f64>() < self.newest {
+                active.len() - 1
+            } else {
+                rng.gen_range(0, active.len())
+            };
+            let curr = active[i];
+            let ads: Vec<Posn> = cells.neighbours(curr)
+                .into_iter()
+                .filter(|&p| !cells.is_open(p))
+                .collect();
+            if let Some(&next) = rng.choose(&ads) {
+                cells.connect(curr, next);
+                active.push(next);
+            } else {
+                active.remove(i);
+            }
+        }
+    }
+}
+
+/// Binary tree: every cell opens towards one of its neighbours above or to
+/// the left. Fast, but leaves long open corridors along the top and left
+/// edges.
+pub struct BinaryTree;
+
+impl MazeGenerator for BinaryTree {
+    fn carve<R: Rng>(&self, cells: &mut Cells, rng: &mut R) {
+        for row in 0..cells.height as i32 {
+            for col in 0..cells.width as i32 {
+                let p = Posn { row, col };
+                cells.open(p);
+                let ads: Vec<Posn> = cells.neighbours(p)
+                    .into_iter()
+                    .filter(|&n| n < p)
+                    .collect();
+                if let Some(&next) = rng.choose(&ads) {
+                    cells.connect(p, next);
+                }
+            }
+        }
+    }
+}
+
+/// The generators that can be picked by name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    HuntAndKill,
+    RecursiveBacktracker,
+    Prim,
+    Kruskal,
+    Wilson,
+    Eller,
+    GrowingTree,
+    BinaryTree,
+    Dungeon,
+}
+
+pub const ALGORITHMS: [Algorithm; 9] = [Algorithm::HuntAndKill,
+                                        Algorithm::RecursiveBacktracker,
+                                        Algorithm::Prim,
+                                        Algorithm::Kruskal,
+                                        Algorithm::Wilson,
+                                        Algorithm::Eller,
+                                        Algorithm::GrowingTree,
+                                        Algorithm::BinaryTree,
+                                        Algorithm::Dungeon];
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Algorithm::HuntAndKill => "hunt-and-kill",
+            Algorithm::RecursiveBacktracker => "backtracker",
+            Algorithm::Prim => "prim",
+            Algorithm::Kruskal => "kruskal",
+            Algorithm::Wilson => "wilson",
+            Algorithm::Eller => "eller",
+            Algorithm::GrowingTree => "growing-tree",
+            Algorithm::BinaryTree => "binary-tree",
+            Algorithm::Dungeon => "dungeon",
+        }
+    }
+
+    pub fn generate<R: Rng>(&self,
+                            shape: Shape,
+                            height: usize,
+                            width: usize,
+                            wrap: bool,
+                            rng: &mut R)
+                            -> Maze {
+        match *self {
+            Algorithm::HuntAndKill => HuntAndKill.generate(shape, height, width, wrap, rng),
+            Algorithm::RecursiveBacktracker => {
+                RecursiveBacktracker.generate(shape, height, width, wrap, rng)
+            }
+            Algorithm::Prim => Prim.generate(shape, height, width, wrap, rng),
+            Algorithm::Kruskal => Kruskal.generate(shape, height, width, wrap, rng),
+            Algorithm::Wilson => Wilson.generate(shape, height, width, wrap, rng),
+            Algorithm::Eller => Eller.generate(shape, height, width, wrap, rng),
+            Algorithm::GrowingTree => {
+                GrowingTree::default().generate(shape, height, width, wrap, rng)
+            }
+            Algorithm::BinaryTree => BinaryTree.generate(shape, height, width, wrap, rng),
+            Algorithm::Dungeon => {
+                Dungeon::default().generate(shape, height, width, wrap, rng)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Algorithm, String> {
+        ALGORITHMS.iter()
+            .find(|a| a.name() == s)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<&str> = ALGORITHMS.iter().map(|a| a.name()).collect();
+                format!("Unknown generator '{}', expected one of: {}",
+                        s,
+                        names.join(", "))
+            })
+    }
+}
+
+#[test]
+fn test_generators_make_perfect_mazes() {
+    use rand::{SeedableRng, XorShiftRng};
+    use std::collections::HashSet;
+
+    let (height, width) = (7, 11);
+    let shapes = [Shape::Square, Shape::Hex];
+    // Dungeons leave rock between the rooms, so they aren't perfect mazes.
+    for algorithm in ALGORITHMS.iter().filter(|&&a| a != Algorithm::Dungeon) {
+        for &shape in &shapes {
+            let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+            let maze = algorithm.generate(shape, height, width, false, &mut rng);
+            assert_eq!(maze.bounds(), (2 * height + 1, 2 * width + 1));
+
+            // A spanning tree over n cells has n - 1 passages, plus the exit.
+            let floors =
+                maze.map.iter().filter(|&(_, t)| t != Tile::Wall).count();
+            let cells = height * width;
+            assert_eq!(floors, cells + cells - 1 + 1, "{} {}", algorithm, shape);
+
+            let mut seen = HashSet::new();
+            let mut todo = vec![Posn { row: 1, col: 1 }];
+            while let Some(p) = todo.pop() {
+                if maze[&p] == Tile::Wall || !seen.insert(p) {
+                    continue;
+                }
+                todo.extend(shape.topology().neighbours(p));
+            }
+            assert_eq!(seen.len(), floors, "{} {}", algorithm, shape);
+        }
+    }
+}
+
+#[test]
+fn test_wrapped_mazes_cross_the_seam() {
+    use rand::{SeedableRng, XorShiftRng};
+    use std::collections::HashSet;
+
+    let (height, width) = (6, 8);
+    for algorithm in ALGORITHMS.iter().filter(|&&a| a != Algorithm::Dungeon) {
+        for &shape in &[Shape::Square, Shape::Hex] {
+            let mut rng = XorShiftRng::from_seed([4, 3, 2, 1]);
+            let maze = algorithm.generate(shape, height, width, true, &mut rng);
+            assert_eq!(maze.bounds(), (2 * height, 2 * width));
+
+            let floors =
+                maze.map.iter().filter(|&(_, t)| t != Tile::Wall).count();
+            let cells = height * width;
+            assert_eq!(floors, cells + cells - 1 + 1, "{} {}", algorithm, shape);
+
+            let mut seen = HashSet::new();
+            let mut todo = vec![Posn { row: 1, col: 1 }];
+            while let Some(p) = todo.pop() {
+                if maze[&p] == Tile::Wall || !seen.insert(p) {
+                    continue;
+                }
+                todo.extend(maze.neighbours(p));
+            }
+            assert_eq!(seen.len(), floors, "{} {}", algorithm, shape);
+        }
+    }
+
+    // Eller's and the binary tree never look across the seam, but the
+    // others should use it somewhere.
+    let mut rng = XorShiftRng::from_seed([4, 3, 2, 1]);
+    let maze = Algorithm::RecursiveBacktracker.generate(Shape::Square, 10, 10, true, &mut rng);
+    let seam = (1..20).filter(|&i| {
+            maze[&Posn { row: 0, col: i }] == Tile::Floor ||
+            maze[&Posn { row: i, col: 0 }] == Tile::Floor
+        })
+        .count();
+    assert!(seam > 0);
+}
+
+#[test]
+fn test_algorithm_from_str() {
+    for algorithm in &ALGORITHMS {
+        assert_eq!(algorithm.name().parse::<Algorithm>(), Ok(*algorithm));
+    }
+    assert!("nonsense".parse::<Algorithm>().is_err());
+}
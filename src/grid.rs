@@ -1,5 +1,6 @@
 use posn::Posn;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Grid<T>(pub Vec<Vec<T>>);
 
 impl<T> Grid<T> {
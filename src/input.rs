@@ -0,0 +1,342 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::io::AsRawFd;
+use std::str::{self, FromStr};
+
+/// How long to wait for the rest of an escape sequence before deciding
+/// Esc was pressed on its own.
+const ESC_TIMEOUT_MS: i32 = 50;
+
+/// One key press, however the terminal spelt it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// A letter (or `@`, `[`, `\`, `]`, `^`, `_`) with Ctrl held down.
+    Ctrl(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// F1 to F12.
+    F(u8),
+    Enter,
+    Tab,
+    Backspace,
+    Esc,
+    /// A sequence we don't know, kept so it can be logged.
+    Unknown(Vec<u8>),
+}
+
+/// Keys with a name rather than a character.
+const NAMED_KEYS: [(&str, Key); 12] = [("up", Key::Up),
+                                       ("down", Key::Down),
+                                       ("left", Key::Left),
+                                       ("right", Key::Right),
+                                       ("home", Key::Home),
+                                       ("end", Key::End),
+                                       ("page-up", Key::PageUp),
+                                       ("page-down", Key::PageDown),
+                                       ("insert", Key::Insert),
+                                       ("delete", Key::Delete),
+                                       ("enter", Key::Enter),
+                                       ("tab", Key::Tab)];
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(&(name, _)) = NAMED_KEYS.iter().find(|named| named.1 == *self) {
+            return f.write_str(name);
+        }
+        match *self {
+            Key::Char(' ') => f.write_str("space"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Ctrl(c) => write!(f, "ctrl-{}", c),
+            Key::F(n) => write!(f, "f{}", n),
+            Key::Backspace => f.write_str("backspace"),
+            Key::Esc => f.write_str("esc"),
+            Key::Unknown(ref bytes) => write!(f, "{:?}", String::from_utf8_lossy(bytes)),
+            _ => unreachable!("Named keys are handled above"),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Key, String> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key::Char(c));
+        }
+        if let Some((_, key)) = NAMED_KEYS.iter().find(|named| named.0 == s) {
+            return Ok(key.clone());
+        }
+        let ctrl = s.strip_prefix("ctrl-").and_then(|c| {
+            let c = c.parse::<char>().ok()?.to_ascii_lowercase();
+            if c.is_ascii_lowercase() || "@[\\]^_".contains(c) { Some(Key::Ctrl(c)) } else { None }
+        });
+        let function = s.strip_prefix('f')
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| (1..=12).contains(n))
+            .map(Key::F);
+        match s {
+            "space" => Ok(Key::Char(' ')),
+            "backspace" => Ok(Key::Backspace),
+            "esc" => Ok(Key::Esc),
+            _ => {
+                ctrl.or(function).ok_or_else(|| {
+                    format!("Unknown key '{}', expected a character, up, down, left, right, \
+                             home, end, page-up, page-down, insert, delete, enter, tab, \
+                             backspace, esc, space, f1 to f12 or ctrl-<letter>",
+                            s)
+                })
+            }
+        }
+    }
+}
+
+/// The key for the `~` sequences, `ESC [ <n> ~`.
+fn tilde_key(n: u32) -> Option<Key> {
+    match n {
+        1 | 7 => Some(Key::Home),
+        2 => Some(Key::Insert),
+        3 => Some(Key::Delete),
+        4 | 8 => Some(Key::End),
+        5 => Some(Key::PageUp),
+        6 => Some(Key::PageDown),
+        11..=15 => Some(Key::F((n - 10) as u8)),
+        17..=21 => Some(Key::F((n - 11) as u8)),
+        23 | 24 => Some(Key::F((n - 12) as u8)),
+        _ => None,
+    }
+}
+
+/// The key for the final letter of a CSI or SS3 sequence.
+fn letter_key(letter: u8) -> Option<Key> {
+    match letter {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'P'..=b'S' => Some(Key::F(letter - b'P' + 1)),
+        _ => None,
+    }
+}
+
+/// `ESC [`, then parameters and a final byte. Modifiers (as in
+/// `ESC [ 1 ; 5 A` for Ctrl-Up) are dropped.
+fn csi(bytes: &[u8]) -> Option<(Key, usize)> {
+    // The Linux console's F1 to F5 are `ESC [ [ A` to `ESC [ [ E`.
+    if bytes.get(2) == Some(&b'[') {
+        let key = match *bytes.get(3)? {
+            letter @ b'A'..=b'E' => Key::F(letter - b'A' + 1),
+            _ => Key::Unknown(bytes[..4].to_vec()),
+        };
+        return Some((key, 4));
+    }
+    let end = 2 + bytes[2..].iter().position(|&b| (0x40..=0x7E).contains(&b))?;
+    let sequence = &bytes[..end + 1];
+    let params = str::from_utf8(&bytes[2..end]).unwrap_or("");
+    let first = params.split(';').next().and_then(|n| n.parse().ok());
+    let key = match bytes[end] {
+        b'~' => first.and_then(tilde_key),
+        letter => letter_key(letter),
+    };
+    Some((key.unwrap_or_else(|| Key::Unknown(sequence.to_vec())), sequence.len()))
+}
+
+/// `ESC O` and one byte: what some terminals send for the arrows, F1 to F4
+/// and the number pad.
+fn ss3(bytes: &[u8]) -> Option<(Key, usize)> {
+    let key = match *bytes.get(2)? {
+        b'M' => Key::Enter,
+        digit @ b'p'..=b'y' => Key::Char((digit - b'p' + b'0') as char),
+        letter => letter_key(letter).unwrap_or_else(|| Key::Unknown(bytes[..3].to_vec())),
+    };
+    Some((key, 3))
+}
+
+/// Decodes the first key in `bytes`, returning it and how many bytes it
+/// took, or `None` if the bytes so far are only the start of a key. Once
+/// nothing more is coming, `complete` makes it settle for what's there.
+pub fn decode(bytes: &[u8], complete: bool) -> Option<(Key, usize)> {
+    let first = *bytes.first()?;
+    let decoded = match first {
+        0x1B => {
+            match bytes.get(1) {
+                Some(&b'[') => csi(bytes),
+                Some(&b'O') => ss3(bytes),
+                // Alt and a key, or Esc pressed twice: either way, Esc first.
+                Some(_) => Some((Key::Esc, 1)),
+                None => None,
+            }
+        }
+        b'\r' | b'\n' => Some((Key::Enter, 1)),
+        b'\t' => Some((Key::Tab, 1)),
+        0x7F | 0x08 => Some((Key::Backspace, 1)),
+        0x00 => Some((Key::Ctrl('@'), 1)),
+        0x01..=0x1A => Some((Key::Ctrl((first - 1 + b'a') as char), 1)),
+        0x1C..=0x1F => Some((Key::Ctrl((first + 0x40) as char), 1)),
+        _ => {
+            let width = match first {
+                0x00..=0x7F => 1,
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => return Some((Key::Unknown(vec![first]), 1)),
+            };
+            bytes.get(..width).map(|char_bytes| match str::from_utf8(char_bytes) {
+                Ok(s) => (Key::Char(s.chars().next().unwrap()), width),
+                Err(_) => (Key::Unknown(vec![first]), 1),
+            })
+        }
+    };
+    match decoded {
+        None if complete && first == 0x1B => Some((Key::Esc, 1)),
+        None if complete => Some((Key::Unknown(vec![first]), 1)),
+        decoded => decoded,
+    }
+}
+
+/// Splits a stream of bytes from the terminal into keys, however the reads
+/// happen to cut it up.
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    /// The next whole key, if there is one yet.
+    pub fn next_key(&mut self) -> Option<Key> {
+        self.take(false)
+    }
+
+    /// The next key, taking the start of an escape sequence as it stands:
+    /// for when the rest hasn't turned up in time.
+    pub fn flush(&mut self) -> Option<Key> {
+        self.take(true)
+    }
+
+    /// Whether there are bytes waiting for the rest of a key.
+    pub fn is_waiting(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn take(&mut self, complete: bool) -> Option<Key> {
+        let (key, used) = decode(&self.pending, complete)?;
+        self.pending.drain(..used);
+        Some(key)
+    }
+}
+
+/// Reads keys from the terminal, one at a time.
+pub struct Keys {
+    file: File,
+    decoder: Decoder,
+}
+
+impl Keys {
+    pub fn new(file: File) -> Keys {
+        Keys {
+            file,
+            decoder: Decoder::default(),
+        }
+    }
+
+    /// Waits for the next key.
+    pub fn read_key(&mut self) -> io::Result<Key> {
+        loop {
+            if let Some(key) = self.decoder.next_key() {
+                return Ok(key);
+            }
+            if self.decoder.is_waiting() && !self.ready(ESC_TIMEOUT_MS)? {
+                return Ok(self.decoder.flush().expect("Waiting on no bytes"));
+            }
+            let mut input: [u8; 64] = [0; 64];
+            let bytes = self.file.read(&mut input)?;
+            if bytes == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The terminal closed"));
+            }
+            self.decoder.feed(&input[..bytes]);
+        }
+    }
+
+    /// Whether there's something to read within `timeout_ms`.
+    fn ready(&self, timeout_ms: i32) -> io::Result<bool> {
+        let mut poll = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut poll, 1, timeout_ms) } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+}
+
+#[test]
+fn test_decoder() {
+    fn keys(reads: &[&[u8]]) -> Vec<Key> {
+        let mut decoder = Decoder::default();
+        let mut keys = vec![];
+        for read in reads {
+            decoder.feed(read);
+            while let Some(key) = decoder.next_key() {
+                keys.push(key);
+            }
+        }
+        while let Some(key) = decoder.flush() {
+            keys.push(key);
+        }
+        keys
+    }
+    use self::Key::*;
+
+    // Fast typing, with two keys in one read, and an arrow split across two.
+    assert_eq!(keys(&[b"wd", b"\x1B[", b"A\x1B[Bq"]),
+               vec![Char('w'), Char('d'), Up, Down, Char('q')]);
+    // xterm, rxvt and the Linux console all spell these differently.
+    assert_eq!(keys(&[b"\x1B[H\x1B[1~\x1B[7~\x1BOH", b"\x1B[F\x1B[4~\x1B[8~\x1BOF"]),
+               vec![Home, Home, Home, Home, End, End, End, End]);
+    assert_eq!(keys(&[b"\x1B[5~\x1B[6~\x1B[2~\x1B[3~\x1BOA\x1B[1;5C"]),
+               vec![PageUp, PageDown, Insert, Delete, Up, Right]);
+    assert_eq!(keys(&[b"\x1BOP\x1B[[B\x1B[15~\x1B[24~\x1B[1;2S"]),
+               vec![F(1), F(2), F(5), F(12), F(4)]);
+    // The number pad with Num Lock on, in application mode.
+    assert_eq!(keys(&[b"\x1BOw\x1BOr\x1BOM"]), vec![Char('7'), Char('2'), Enter]);
+    assert_eq!(keys(&[b"\x01\x13\x1A\r\t\x7F"]),
+               vec![Ctrl('a'), Ctrl('s'), Ctrl('z'), Enter, Tab, Backspace]);
+    // A lone Esc, then [A typed afterwards, isn't an arrow.
+    assert_eq!(keys(&[b"\x1B"]), vec![Esc]);
+    let mut decoder = Decoder::default();
+    decoder.feed(b"\x1B");
+    assert_eq!(decoder.next_key(), None);
+    assert_eq!(decoder.flush(), Some(Esc));
+    decoder.feed(b"[A");
+    assert_eq!(decoder.next_key(), Some(Char('[')));
+    assert_eq!(decoder.next_key(), Some(Char('A')));
+    // Esc twice, Alt-x, an unknown sequence and a character split in two.
+    assert_eq!(keys(&[b"\x1B\x1B[D\x1Bx\x1B[99~", b"\xC3", b"\xA9"]),
+               vec![Esc, Left, Esc, Char('x'), Unknown(b"\x1B[99~".to_vec()), Char('é')]);
+
+    assert_eq!("page-up".parse(), Ok(PageUp));
+    assert_eq!("ctrl-X".parse(), Ok(Ctrl('x')));
+    assert_eq!("f11".parse(), Ok(F(11)));
+    assert!("f13".parse::<Key>().is_err());
+    assert_eq!(Char(' ').to_string(), "space");
+    assert_eq!(Ctrl('w').to_string(), "ctrl-w");
+}
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::str::FromStr;
+
+use direction::Direction;
+use direction::Direction::*;
+use input::Key;
+use topology::Topology;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Move(Direction),
+    Pathfind,
+    Save,
+    /// List the keys.
+    Help,
+    Quit,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self {
+            Command::Move(dir) => dir.name(),
+            Command::Pathfind => "pathfind",
+            Command::Save => "save",
+            Command::Help => "help",
+            Command::Quit => "quit",
+        })
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Command, String> {
+        match s {
+            "pathfind" => Ok(Command::Pathfind),
+            "save" => Ok(Command::Save),
+            "help" => Ok(Command::Help),
+            "quit" => Ok(Command::Quit),
+            _ => {
+                s.parse()
+                    .map(Command::Move)
+                    .map_err(|_| {
+                        format!("Unknown command '{}', expected a direction, pathfind, save, help \
+                                 or quit",
+                                s)
+                    })
+            }
+        }
+    }
+}
+
+/// A built in set of keys to start from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// `w`, `a`, `s` and `d`, with `y`, `u`, `b` and `n` for the diagonals.
+    Wasd,
+    /// `h`, `j`, `k` and `l`, with the same diagonals as `Wasd`.
+    Vi,
+    /// The number pad, with Num Lock on or off.
+    Numpad,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s {
+            "wasd" => Ok(Preset::Wasd),
+            "vi" => Ok(Preset::Vi),
+            "numpad" => Ok(Preset::Numpad),
+            _ => Err(format!("Unknown keys '{}', expected wasd, vi or numpad", s)),
+        }
+    }
+}
+
+/// Which key does what.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(Key, Command)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::preset(Preset::Wasd)
+    }
+}
+
+impl Keymap {
+    pub fn preset(preset: Preset) -> Keymap {
+        use self::Command::*;
+        let moves: [(Key, Direction); 12] = match preset {
+            Preset::Wasd | Preset::Vi => {
+                let (up, left, down, right) = if preset == Preset::Wasd {
+                    ('w', 'a', 's', 'd')
+                } else {
+                    ('k', 'h', 'j', 'l')
+                };
+                [(Key::Char(up), North),
+                 (Key::Char(down), South),
+                 (Key::Char(left), West),
+                 (Key::Char(right), East),
+                 // Diagonals, for the hex grid.
+                 (Key::Char('y'), NorthWest),
+                 (Key::Char('u'), NorthEast),
+                 (Key::Char('b'), SouthWest),
+                 (Key::Char('n'), SouthEast),
+                 (Key::Up, North),
+                 (Key::Down, South),
+                 (Key::Left, West),
+                 (Key::Right, East)]
+            }
+            Preset::Numpad => {
+                [(Key::Char('8'), North),
+                 (Key::Char('2'), South),
+                 (Key::Char('4'), West),
+                 (Key::Char('6'), East),
+                 (Key::Char('7'), NorthWest),
+                 (Key::Char('9'), NorthEast),
+                 (Key::Char('1'), SouthWest),
+                 (Key::Char('3'), SouthEast),
+                 // The arrows double as 8, 2, 4 and 6 without Num Lock.
+                 (Key::Home, NorthWest),
+                 (Key::PageUp, NorthEast),
+                 (Key::End, SouthWest),
+                 (Key::PageDown, SouthEast)]
+            }
+        };
+        let mut keymap = Keymap {
+            bindings: moves.iter().map(|&(ref key, dir)| (key.clone(), Move(dir))).collect(),
+        };
+        if preset == Preset::Numpad {
+            let arrows = vec![(Key::Up, North),
+                              (Key::Down, South),
+                              (Key::Left, West),
+                              (Key::Right, East)];
+            for (key, dir) in arrows {
+                keymap.bind(key, Some(Move(dir)));
+            }
+        }
+        for &(key, command) in &[('p', Pathfind), ('S', Save), ('?', Help), ('q', Quit)] {
+            keymap.bind(Key::Char(key), Some(command));
+        }
+        keymap
+    }
+
+    pub fn command(&self, key: &Key) -> Option<Command> {
+        self.bindings.iter().find(|&(k, _)| k == key).map(|&(_, command)| command)
+    }
+
+    /// Makes `key` do `command`, instead of whatever it did before, or
+    /// nothing at all.
+    pub fn bind(&mut self, key: Key, command: Option<Command>) {
+        self.bindings.retain(|(k, _)| *k != key);
+        if let Some(command) = command {
+            self.bindings.push((key, command));
+        }
+    }
+
+    /// The keys for `command`.
+    pub fn keys(&self, command: Command) -> Vec<&Key> {
+        self.bindings.iter().filter(|&&(_, c)| c == command).map(|(key, _)| key).collect()
+    }
+
+    /// A line for each command with a key, leaving out moves `topology`
+    /// doesn't have.
+    pub fn help(&self, topology: &dyn Topology) -> Vec<String> {
+        let moves = topology.directions().iter().map(|&dir| Command::Move(dir));
+        let others = vec![Command::Pathfind, Command::Save, Command::Help, Command::Quit];
+        moves.chain(others)
+            .filter_map(|command| {
+                let keys: Vec<String> = self.keys(command).iter().map(|k| k.to_string()).collect();
+                if keys.is_empty() {
+                    None
+                } else {
+                    Some(format!("{:>10}  {}", command, keys.join(", ")))
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_keymap() {
+    use topology::Shape;
+
+    let mut keymap = Keymap::default();
+    assert_eq!(keymap.command(&Key::Left), Some(Command::Move(West)));
+    assert_eq!(keymap.command(&Key::Char('k')), None);
+
+    keymap.bind("k".parse().unwrap(), Some("north".parse().unwrap()));
+    keymap.bind("left".parse().unwrap(), Some("quit".parse().unwrap()));
+    keymap.bind(Key::Char('w'), None);
+    assert_eq!(keymap.command(&Key::Char('k')), Some(Command::Move(North)));
+    assert_eq!(keymap.command(&Key::Left), Some(Command::Quit));
+    assert_eq!(keymap.command(&Key::Char('w')), None);
+    assert_eq!(keymap.keys(Command::Quit), vec![&Key::Char('q'), &Key::Left]);
+    assert!("jump".parse::<Command>().is_err());
+
+    let vi = Keymap::preset(Preset::Vi);
+    assert_eq!(vi.command(&Key::Char('l')), Some(Command::Move(East)));
+    assert_eq!(vi.command(&Key::Char('w')), None);
+    let numpad = Keymap::preset(Preset::Numpad);
+    assert_eq!(numpad.command(&Key::Char('7')), Some(Command::Move(NorthWest)));
+    assert_eq!(numpad.command(&Key::Home), Some(Command::Move(NorthWest)));
+    assert_eq!(numpad.command(&Key::Up), Some(Command::Move(North)));
+
+    let help = vi.help(Shape::Square.topology());
+    assert_eq!(help.len(), 8);
+    assert_eq!(help[0], "     north  k, up");
+    assert_eq!(help[7], "      quit  q");
+}
@@ -0,0 +1,427 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::str;
+
+use direction::Direction;
+use direction::Direction::*;
+use maze::binary;
+use maze::{LoadError, Maze, MazeParseError, ParseErrorKind};
+use player::Player;
+use pnm;
+use posn::Posn;
+use tile::Tile;
+use topology::Shape;
+use troll::Troll;
+
+/// The first line of a level file, followed by the format version.
+const MAGIC: &str = "maze-level";
+const VERSION: u32 = 1;
+
+/// Trolls are drawn the same way they look in the game.
+const TROLL_GLYPHS: [(char, Direction); 8] = [('▲', North),
+                                              ('▶', East),
+                                              ('▼', South),
+                                              ('◀', West),
+                                              ('◥', NorthEast),
+                                              ('◢', SouthEast),
+                                              ('◣', SouthWest),
+                                              ('◤', NorthWest)];
+
+/// The player gets the hollow versions. `@` also works, facing the first
+/// way the maze's shape allows.
+const PLAYER_GLYPHS: [(char, Direction); 8] = [('△', North),
+                                               ('▷', East),
+                                               ('▽', South),
+                                               ('◁', West),
+                                               ('◹', NorthEast),
+                                               ('◿', SouthEast),
+                                               ('◺', SouthWest),
+                                               ('◸', NorthWest)];
+
+/// The header of a level file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    pub name: String,
+    pub author: String,
+    /// How many ticks a good run takes.
+    pub par: Option<u64>,
+    pub ruleset: String,
+}
+
+impl Default for Metadata {
+    fn default() -> Metadata {
+        Metadata {
+            name: String::new(),
+            author: String::new(),
+            par: None,
+            ruleset: "classic".to_owned(),
+        }
+    }
+}
+
+impl Metadata {
+    /// Sets a field from a `key: value` header line. Returns false if the
+    /// key isn't one of ours.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool, String> {
+        match key {
+            "name" => self.name = value.to_owned(),
+            "author" => self.author = value.to_owned(),
+            "ruleset" => self.ruleset = value.to_owned(),
+            "par" => self.par = Some(value.parse().map_err(|_| format!("bad par '{}'", value))?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Writes the header lines `Metadata::set` reads back.
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "author: {}", self.author)?;
+        if let Some(par) = self.par {
+            writeln!(f, "par: {}", par)?;
+        }
+        writeln!(f, "ruleset: {}", self.ruleset)
+    }
+}
+
+/// Reads the `wrap` header, which is `yes` or `no`.
+pub fn parse_wrap(value: &str) -> Result<bool, String> {
+    match value {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("wrap should be yes or no, not '{}'", value)),
+    }
+}
+
+/// Splits a `key: value` header line.
+pub fn split_header(line: &str) -> Result<(&str, &str), String> {
+    line.split_once(':')
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .ok_or_else(|| format!("expected 'key: value', got '{}'", line))
+}
+
+/// A maze with everything needed to start playing it.
+///
+/// Level files look like this, with the map after the first blank line:
+///
+/// ```text
+/// maze-level 1
+/// name: Ambush
+/// author: tbelaire
+/// par: 30
+/// ruleset: classic
+///
+/// #######
+/// #△  ◀ X
+/// #######
+/// ```
+///
+/// `shape: hex` and `wrap: yes` can go in the header too. A plain maze of
+/// `#`, spaces and `X` loads as a level with no header, trolls or player.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Level {
+    pub metadata: Option<Metadata>,
+    /// With any trolls the level starts with.
+    pub maze: Maze,
+    pub player: Option<Player>,
+}
+
+/// What `Level::read` makes of a file, problems and all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Draft {
+    pub level: Level,
+    /// The line of the file the map starts on.
+    pub first_line: usize,
+    /// Everything wrong with the map that `Level::parse` would refuse, in the
+    /// order it turned up.
+    pub problems: Vec<MazeParseError>,
+}
+
+fn lookup(glyphs: &[(char, Direction)], c: char) -> Option<Direction> {
+    glyphs.iter().find(|&&(g, _)| g == c).map(|&(_, dir)| dir)
+}
+
+fn glyph(glyphs: &[(char, Direction)], dir: Direction) -> char {
+    glyphs.iter().find(|&&(_, d)| d == dir).map(|&(g, _)| g).unwrap()
+}
+
+/// Writes the level file `Level::parse` reads back. Dead trolls are left
+/// out, since levels can't have any.
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", MAGIC, VERSION)?;
+        if let Some(ref metadata) = self.metadata {
+            write!(f, "{}", metadata)?;
+        }
+        if self.maze.shape != Shape::Square {
+            writeln!(f, "shape: {}", self.maze.shape)?;
+        }
+        if self.maze.wrap {
+            writeln!(f, "wrap: yes")?;
+        }
+        writeln!(f)?;
+
+        let mut rows: Vec<Vec<char>> = (0..self.maze.bounds().0)
+            .map(|row| self.maze.text_row(row).chars().collect())
+            .collect();
+        for (pos, troll) in self.maze.trolls.iter().filter(|&(_, t)| t.alive) {
+            rows[pos.row as usize][pos.col as usize] = glyph(&TROLL_GLYPHS, troll.dir);
+        }
+        if let Some(ref player) = self.player {
+            rows[player.pos.row as usize][player.pos.col as usize] =
+                glyph(&PLAYER_GLYPHS, player.dir);
+        }
+        for row in rows {
+            writeln!(f, "{}", row.into_iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+impl Level {
+    /// Loads a level file, a plain maze, or a maze drawn as a Netpbm image.
+    pub fn from_file(filename: &str) -> Result<Level, LoadError> {
+        let mut bytes = vec![];
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        Level::from_bytes(&bytes)
+    }
+
+    /// Reads the contents of any file `from_file` loads, telling the
+    /// formats apart by how they start.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Level, LoadError> {
+        let maze = if pnm::is_netpbm(bytes) {
+            pnm::parse(bytes)?
+        } else if binary::is_binary(bytes) {
+            binary::read(bytes)?
+        } else {
+            let text = str::from_utf8(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            return Ok(Level::parse(text)?);
+        };
+        Ok(Level {
+            metadata: None,
+            maze,
+            player: None,
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Level, MazeParseError> {
+        let draft = Level::read(text)?;
+        match draft.problems.into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(draft.level),
+        }
+    }
+
+    /// Reads a level like `parse`, but only gives up on problems with the
+    /// header or the people in it. Anything wrong with the map itself is
+    /// collected in the `Draft`, so it can all be reported at once.
+    pub fn read(text: &str) -> Result<Draft, MazeParseError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let header_error = |line: usize, reason: String| {
+            Err(MazeParseError {
+                line: line + 1,
+                column: 1,
+                kind: ParseErrorKind::BadHeader(reason),
+            })
+        };
+
+        let version = match lines.first().and_then(|l| l.strip_prefix(MAGIC)) {
+            Some(version) => version.trim(),
+            None => {
+                let (maze, mut problems) =
+                    Maze::read_rows(&lines, 1, Shape::Square, false, |_, _| None)?;
+                problems.extend(maze.check_exits(1).err());
+                return Ok(Draft {
+                    level: Level {
+                        metadata: None,
+                        maze,
+                        player: None,
+                    },
+                    first_line: 1,
+                    problems,
+                });
+            }
+        };
+        if version.parse() != Ok(VERSION) {
+            return Err(MazeParseError {
+                line: 1,
+                column: MAGIC.len() + 2,
+                kind: ParseErrorKind::UnknownVersion(version.to_owned()),
+            });
+        }
+
+        let mut metadata = Metadata::default();
+        let (mut shape, mut wrap) = (Shape::Square, false);
+        let mut i = 1;
+        while i < lines.len() && !lines[i].is_empty() {
+            let header = split_header(lines[i]).and_then(|(key, value)| {
+                match key {
+                    "shape" => shape = value.parse()?,
+                    "wrap" => wrap = parse_wrap(value)?,
+                    _ => {
+                        if !metadata.set(key, value)? {
+                            return Err(format!("unknown key '{}'", key));
+                        }
+                    }
+                }
+                Ok(())
+            });
+            if let Err(reason) = header {
+                return header_error(i, reason);
+            }
+            i += 1;
+        }
+
+        let mut trolls = vec![];
+        let mut players = vec![];
+        let first_line = i + 2;
+        let map_lines = &lines[(i + 1).min(lines.len())..];
+        let (mut maze, mut problems) = Maze::read_rows(map_lines,
+                                                       first_line,
+                                                       shape,
+                                                       wrap,
+                                                       |c, pos| {
+            if let Some(dir) = lookup(&TROLL_GLYPHS, c) {
+                trolls.push((pos, dir));
+            } else if let Some(dir) = lookup(&PLAYER_GLYPHS, c) {
+                players.push((pos, Some(dir)));
+            } else if c == '@' {
+                players.push((pos, None));
+            } else {
+                return None;
+            }
+            Some(Tile::Floor)
+        })?;
+        problems.extend(maze.check_exits(first_line).err());
+
+        let topology = shape.topology();
+        let facing_error = |pos: Posn, kind| {
+            Err(MazeParseError {
+                line: first_line + pos.row as usize,
+                column: pos.col as usize + 1,
+                kind,
+            })
+        };
+        for &(pos, dir) in &trolls {
+            if topology.offset(dir).is_none() {
+                return facing_error(pos, ParseErrorKind::BadFacing(dir));
+            }
+            maze.add_troll(pos, Troll::new(dir));
+        }
+        if players.len() > 1 {
+            return facing_error(players[1].0, ParseErrorKind::DuplicatePlayer);
+        }
+        let player = match players.first() {
+            Some(&(pos, Some(dir))) if topology.offset(dir).is_none() => {
+                return facing_error(pos, ParseErrorKind::BadFacing(dir));
+            }
+            Some(&(pos, dir)) => {
+                Some(Player {
+                    pos,
+                    dir: dir.unwrap_or(topology.directions()[0]),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Draft {
+            level: Level {
+                metadata: Some(metadata),
+                maze,
+                player,
+            },
+            first_line,
+            problems,
+        })
+    }
+}
+
+#[test]
+fn test_parse_level() {
+    let text = "maze-level 1\r\n\
+                name: Ambush\r\n\
+                author: tbelaire\r\n\
+                par: 30\r\n\
+                \r\n\
+                #######\r\n\
+                #△  ◀ X\r\n\
+                #######\r\n";
+    let level = Level::parse(text).unwrap();
+    let metadata = level.metadata.unwrap();
+    assert_eq!(metadata.name, "Ambush");
+    assert_eq!(metadata.author, "tbelaire");
+    assert_eq!(metadata.par, Some(30));
+    assert_eq!(metadata.ruleset, "classic");
+    assert_eq!(level.player,
+               Some(Player {
+                   pos: Posn { row: 1, col: 1 },
+                   dir: North,
+               }));
+    let troll = Posn { row: 1, col: 4 };
+    assert_eq!(level.maze.trolls[&troll].dir, West);
+    assert_eq!(level.maze[&troll], Tile::Floor);
+
+    // The plain format still loads.
+    let plain = Level::parse("#####\nX   #\n#####\n").unwrap();
+    assert_eq!(plain.metadata, None);
+    assert_eq!(plain.player, None);
+    assert!(plain.maze.trolls.is_empty());
+
+    let error = |text: &str| Level::parse(text).unwrap_err();
+    assert_eq!(error("maze-level 2\n\n###\nX #\n###\n").kind,
+               ParseErrorKind::UnknownVersion("2".to_owned()));
+    assert_eq!(error("maze-level 1\ncolour: red\n\n###\nX #\n###\n").line, 2);
+    let two_players = error("maze-level 1\n\n####\nX@@#\n####\n");
+    assert_eq!((two_players.line, two_players.column, two_players.kind),
+               (4, 3, ParseErrorKind::DuplicatePlayer));
+    assert_eq!(error("maze-level 1\nshape: hex\n\n####\nX▲ #\n####\n").kind,
+               ParseErrorKind::BadFacing(North));
+}
+
+#[test]
+fn test_write_level() {
+    let text = "maze-level 1\n\
+                name: Ambush\n\
+                author: tbelaire\n\
+                ruleset: classic\n\
+                shape: hex\n\
+                wrap: yes\n\
+                \n\
+                ## ###\n\
+                X◹ ◀ #\n\
+                \x20#  .*\n";
+    let level = Level::parse(text).unwrap();
+    assert_eq!(level.to_string(), text);
+    assert_eq!(Level::parse(&level.to_string()), Ok(level));
+}
+
+#[test]
+fn test_load_error() {
+    use pnm::PnmError;
+
+    match Level::from_file("levels/no-such-level.txt") {
+        Err(LoadError::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        other => panic!("expected an I/O error, got {:?}", other),
+    }
+    match Level::from_bytes(b"#####\n#   X\n# ###\n") {
+        Err(LoadError::Parse(e)) => {
+            assert_eq!((e.line, e.kind), (3, ParseErrorKind::MissingBorder))
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+    match Level::from_bytes(b"P2 3 3 15\n0 0 0\n0 15 15\n0 0 0\n") {
+        Err(LoadError::Image(PnmError::Map(e))) => {
+            assert_eq!(e.kind, ParseErrorKind::MissingBorder)
+        }
+        other => panic!("expected an image error, got {:?}", other),
+    }
+    match Level::from_bytes(b"#\xFF") {
+        Err(LoadError::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        other => panic!("expected an I/O error, got {:?}", other),
+    }
+}
@@ -1,22 +1,28 @@
 extern crate ansi_term;
 extern crate termios;
+extern crate libc;
 #[macro_use]
 extern crate log;
 extern crate fern;
 extern crate time;
 extern crate rand;
-#[macro_use]
-extern crate text_io;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::io;
 use std::io::prelude::*;
 use std::fs::File;
-use std::os::unix::io::AsRawFd;
 use std::mem;
+use std::fmt;
+use std::str::FromStr;
 
 use rand::Rng;
 
+mod braid;
 mod direction;
+mod dungeon;
+mod exits;
+mod game;
+mod generator;
 mod maze;
 mod player;
 mod posn;
@@ -25,21 +31,41 @@ mod tile;
 mod troll;
 mod pathfind;
 mod grid;
+mod level;
+mod topology;
+mod rng;
+mod tower;
+mod svg;
+mod pnm;
+mod campaign;
+mod sokoban;
+mod validate;
+mod input;
+mod keymap;
+mod config;
+mod packed;
+mod terminal;
 
-use direction::{Direction, North, South, East, West};
-use maze::Maze;
+use campaign::Campaign;
+use exits::ExitPlacement;
+use game::GameState;
+use generator::Algorithm;
+use input::{Key, Keys};
+use keymap::{Command, Keymap, Preset};
+use level::{Level, Metadata};
+use maze::{analysis, binary};
 use player::Player;
-use screen::move_cursor;
+use posn::Posn;
+use rng::GameRng;
+use screen::{move_cursor, RenderMode};
+use svg::{SvgOptions, WallStyle};
+use terminal::Terminal;
 use tile::Tile;
+use topology::Shape;
+use tower::{Location, Tower};
+use troll::Troll;
 
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum Command {
-    Move(Direction),
-    Pathfind,
-    Quit,
-}
-
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum QuitReason {
     Eaten,
@@ -48,24 +74,581 @@ enum QuitReason {
     Error,
 }
 
-fn parse_keystroke(input: &[u8]) -> Option<Command> {
-    use Command::*;
-    match input {
-        b"\x1B[A" => Some(Move(North)),
-        b"\x1B[B" => Some(Move(South)),
-        b"\x1B[C" => Some(Move(East)),
-        b"\x1B[D" => Some(Move(West)),
-        b"w" => Some(Move(North)),
-        b"s" => Some(Move(South)),
-        b"a" => Some(Move(West)),
-        b"d" => Some(Move(East)),
-        b"q" => Some(Quit),
-        b"p" => Some(Pathfind),
-        _ => None,
+/// Rows below the maze used for the position readout and the path.
+const HUD_ROWS: usize = 2;
+
+const USAGE: &str = "\
+Usage: maze [play] [options]     play a maze, asking for its size if there are no options
+       maze print [options]      write the maze to stdout
+       maze generate [options]   write the maze as a level file, to stdout or --output <file>
+       maze solve [options]      print the way out, one step per line
+       maze validate <file>...   check maze and level files
+
+Options:
+  --maze <file>          a level, maze, image or binary maze instead of a new one
+  --size <n>             cells each way, or --width <n> and --height <n>, or --fit
+  --trolls <n>           trolls to add (none unless asked for)
+  --seed <n>             replay the same maze and trolls
+  --generator <name>     --shape square|hex, --floors <n>, --wrap, --braid <percent>
+  --exits <n>            --exit-placement corner|random|farthest
+  --save <file>          --resume <file>, --campaign <dir or manifest>
+  --svg <file>           --ppm <file>, --binary <file>, --analyse
+  --render <mode>        colour, mono or ascii
+";
+
+/// What to do, from the first argument. Anything else plays.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Subcommand {
+    Play,
+    /// Write the maze to stdout with no cursor movement.
+    Print,
+    /// Write the maze as a level file.
+    Generate,
+    /// Print the way out.
+    Solve,
+}
+
+impl FromStr for Subcommand {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Subcommand, String> {
+        match s {
+            "play" => Ok(Subcommand::Play),
+            "print" => Ok(Subcommand::Print),
+            "generate" => Ok(Subcommand::Generate),
+            "solve" => Ok(Subcommand::Solve),
+            _ => Err(format!("Unknown command '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Options {
+    algorithm: Algorithm,
+    seed: Option<u64>,
+    braid: u32,
+    width: Option<usize>,
+    height: Option<usize>,
+    fit_terminal: bool,
+    exit_placement: Option<ExitPlacement>,
+    exits: usize,
+    maze_file: Option<String>,
+    /// Which level of a Sokoban collection to play, counting from 1.
+    level: usize,
+    analyse: bool,
+    shape: Shape,
+    floors: usize,
+    wrap: bool,
+    save_file: Option<String>,
+    resume: Option<String>,
+    svg_file: Option<String>,
+    svg_style: WallStyle,
+    ppm_file: Option<String>,
+    ppm_scale: usize,
+    binary_file: Option<String>,
+    /// Whether pictures show the way out and the trolls.
+    solution: bool,
+    show_trolls: bool,
+    /// How many trolls to add, instead of asking.
+    trolls: Option<usize>,
+    campaign: Option<String>,
+    render: Option<RenderMode>,
+    keys: Preset,
+    /// Where `maze generate` writes the level.
+    output: Option<String>,
+    subcommand: Subcommand,
+    /// Whether the game was started with no arguments at all, so the maze
+    /// size and trolls are asked for.
+    interactive: bool,
+}
+
+fn next_value<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Result<String, String> {
+    args.next().ok_or(format!("{} needs a value", arg))
+}
+
+fn parse_number<T: FromStr>(arg: &str, value: &str) -> Result<T, String>
+    where T::Err: fmt::Display
+{
+    value.parse().map_err(|e| format!("Bad value '{}' for {}: {}", value, arg, e))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        algorithm: Algorithm::HuntAndKill,
+        seed: None,
+        braid: 0,
+        width: None,
+        height: None,
+        fit_terminal: false,
+        exit_placement: None,
+        exits: 1,
+        maze_file: None,
+        level: 1,
+        analyse: false,
+        shape: Shape::Square,
+        floors: 1,
+        wrap: false,
+        save_file: None,
+        resume: None,
+        svg_file: None,
+        svg_style: WallStyle::Cells,
+        ppm_file: None,
+        ppm_scale: 4,
+        binary_file: None,
+        solution: false,
+        show_trolls: false,
+        trolls: None,
+        campaign: None,
+        render: None,
+        keys: Preset::Wasd,
+        output: None,
+        subcommand: Subcommand::Play,
+        interactive: false,
+    };
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "-g" | "--generator" => {
+                options.algorithm = next_value(&arg, &mut args)?.parse()?;
+            }
+            "--seed" => {
+                options.seed = Some(parse_number(&arg, &next_value(&arg, &mut args)?)?);
+            }
+            "--braid" => {
+                options.braid = parse_number(&arg, &next_value(&arg, &mut args)?)?;
+                if options.braid > 100 {
+                    return Err(format!("Bad braid percentage '{}'", options.braid));
+                }
+            }
+            "--width" => {
+                options.width = Some(parse_number(&arg, &next_value(&arg, &mut args)?)?);
+            }
+            "--height" => {
+                options.height = Some(parse_number(&arg, &next_value(&arg, &mut args)?)?);
+            }
+            "--size" => {
+                let size = parse_number(&arg, &next_value(&arg, &mut args)?)?;
+                options.width = Some(size);
+                options.height = Some(size);
+            }
+            "--fit" => options.fit_terminal = true,
+            "--wrap" => options.wrap = true,
+            "--maze" => options.maze_file = Some(next_value(&arg, &mut args)?),
+            "--level" => options.level = parse_number(&arg, &next_value(&arg, &mut args)?)?,
+            "--save" => options.save_file = Some(next_value(&arg, &mut args)?),
+            "--resume" => options.resume = Some(next_value(&arg, &mut args)?),
+            "--analyse" | "--analyze" => options.analyse = true,
+            "--svg" => options.svg_file = Some(next_value(&arg, &mut args)?),
+            "--svg-style" => options.svg_style = next_value(&arg, &mut args)?.parse()?,
+            "--ppm" => options.ppm_file = Some(next_value(&arg, &mut args)?),
+            "--ppm-scale" => {
+                options.ppm_scale = parse_number(&arg, &next_value(&arg, &mut args)?)?;
+                if options.ppm_scale == 0 {
+                    return Err("There has to be at least one pixel per tile".to_owned());
+                }
+            }
+            "--binary" => options.binary_file = Some(next_value(&arg, &mut args)?),
+            // Named for the SVG at first, which still works.
+            "--solution" | "--svg-solution" => options.solution = true,
+            "--show-trolls" | "--svg-trolls" => options.show_trolls = true,
+            "--trolls" => {
+                options.trolls = Some(parse_number(&arg, &next_value(&arg, &mut args)?)?);
+            }
+            "--campaign" => options.campaign = Some(next_value(&arg, &mut args)?),
+            "--render" => options.render = Some(next_value(&arg, &mut args)?.parse()?),
+            "--keys" => options.keys = next_value(&arg, &mut args)?.parse()?,
+            "-o" | "--output" => options.output = Some(next_value(&arg, &mut args)?),
+            "--shape" => options.shape = next_value(&arg, &mut args)?.parse()?,
+            "--floors" => {
+                options.floors = parse_number(&arg, &next_value(&arg, &mut args)?)?;
+                if options.floors == 0 {
+                    return Err("There has to be at least one floor".to_owned());
+                }
+            }
+            "--exit-placement" => {
+                options.exit_placement = Some(next_value(&arg, &mut args)?.parse()?);
+            }
+            "--exits" => {
+                options.exits = parse_number(&arg, &next_value(&arg, &mut args)?)?;
+                if options.exits == 0 {
+                    return Err("There has to be at least one exit".to_owned());
+                }
+            }
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+    }
+    if options.width == Some(0) || options.height == Some(0) {
+        return Err("Too small".to_owned());
+    }
+    if options.floors > 1 && options.maze_file.is_some() {
+        return Err("--floors can't be used with --maze".to_owned());
+    }
+    if options.wrap && options.maze_file.is_some() {
+        return Err("--wrap can't be used with --maze".to_owned());
+    }
+    let collection = options.maze_file.as_ref().is_some_and(|f| sokoban::is_collection(f));
+    if options.level != 1 && !collection {
+        return Err("--level only works with a --maze that's an .xsb file".to_owned());
+    }
+    if options.campaign.is_some() {
+        let single_game = [(options.maze_file.is_some(), "--maze"),
+                           (options.resume.is_some(), "--resume"),
+                           (options.analyse, "--analyse"),
+                           (options.svg_file.is_some(), "--svg"),
+                           (options.ppm_file.is_some(), "--ppm"),
+                           (options.binary_file.is_some(), "--binary")];
+        if let Some(&(_, arg)) = single_game.iter().find(|&&(set, _)| set) {
+            return Err(format!("{} can't be used with --campaign", arg));
+        }
+    }
+    Ok(options)
+}
+
+/// The largest maze, in cells, that fits on screen with the HUD below it.
+fn fit_to_terminal(shape: Shape) -> Result<(usize, usize), String> {
+    let (rows, cols) = screen::terminal_size()
+        .ok_or("Can't size the maze to the terminal: stdout isn't a terminal")?;
+    let height = rows.saturating_sub(1 + HUD_ROWS) / 2;
+    let mut width = cols / 2;
+    while width > 0 && shape.topology().screen_size(2 * height + 1, 2 * width + 1).1 > cols {
+        width -= 1;
+    }
+    if height < 1 || width < 1 {
+        return Err(format!("The terminal is too small ({} by {})", cols, rows));
+    }
+    Ok((height, width))
+}
+
+/// Asks for a number until it gets one. Fails if stdin runs out first.
+fn prompt_number(prompt: &str) -> Result<usize, String> {
+    let stdin = ::std::io::stdin();
+    loop {
+        print!("{} ", prompt);
+        ::std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err("No answer, so giving up".to_owned());
+        }
+        match line.trim().parse() {
+            Ok(n) => return Ok(n),
+            Err(_) => println!("'{}' isn't a number", line.trim()),
+        }
+    }
+}
+
+/// Loads the level named on the command line, or generates a new maze with
+/// as many floors as asked for. Levels may also say who they're by and
+/// where the player starts.
+fn build_tower(options: &Options,
+               rng: &mut GameRng)
+               -> Result<(Tower, Option<Metadata>, Option<Player>), String> {
+    let mut floors = vec![];
+    let (mut metadata, mut player) = (None, None);
+    if let Some(ref filename) = options.maze_file {
+        let level = if sokoban::is_collection(filename) {
+                sokoban::from_file(filename, options.level)
+            } else {
+                Level::from_file(filename)
+            }
+            .map_err(|e| format!("Couldn't load {}: {}", filename, e))?;
+        floors.push(level.maze);
+        metadata = level.metadata;
+        player = level.player;
+    } else {
+        let (mut height, mut width) = (options.height, options.width);
+        // Without anyone to ask, a game fills the screen.
+        let fit = options.fit_terminal ||
+                  options.subcommand == Subcommand::Play && !options.interactive;
+        if fit && (height.is_none() || width.is_none()) {
+            let (fit_height, fit_width) = fit_to_terminal(options.shape)?;
+            height = height.or(Some(fit_height));
+            width = width.or(Some(fit_width));
+        }
+        let ask = |size: Option<usize>, prompt| match size {
+            Some(size) => Ok(size),
+            None if options.interactive => prompt_number(prompt),
+            None => Err("The maze needs a size: --size, or --width and --height".to_owned()),
+        };
+        let width = ask(width, "Enter the maze width:")?;
+        let height = ask(height, "Enter the maze height:")?;
+        if width < 1 || height < 1 {
+            return Err("Too small".to_owned());
+        }
+        for _ in 0..options.floors {
+            floors.push(options.algorithm
+                .generate(options.shape, height, width, options.wrap, rng));
+        }
+    }
+    for maze in &mut floors {
+        braid::braid(maze, options.braid, rng);
+    }
+    Ok((Tower::stack(floors, rng)?, metadata, player))
+}
+
+/// Where floor `floor` of a picture goes. Towers get one file per floor,
+/// numbered before the extension: `maze-1.svg`, `maze-2.svg` and so on.
+fn floor_filename(filename: &str, floor: usize, tower: &Tower) -> String {
+    if tower.top() == 0 {
+        return filename.to_owned();
+    }
+    match filename.rfind('.') {
+        Some(dot) => format!("{}-{}{}", &filename[..dot], floor + 1, &filename[dot..]),
+        None => format!("{}-{}", filename, floor + 1),
+    }
+}
+
+/// Turns a failure to write to stdout into an error message. If whatever
+/// was reading it has gone, like `head` does once it has its lines, it has
+/// all it wanted, so this exits quietly instead.
+fn stdout_error(e: io::Error) -> String {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        ::std::process::exit(0);
+    }
+    format!("Couldn't write to stdout: {}", e)
+}
+
+fn write_file(filename: &str, contents: &[u8]) -> Result<(), String> {
+    File::create(filename)
+        .and_then(|mut f| f.write_all(contents))
+        .map_err(|e| format!("Couldn't write {}: {}", filename, e))?;
+    writeln!(io::stdout(), "Wrote {}", filename).map_err(stdout_error)
+}
+
+/// Writes each floor of `tower` out as the SVGs, PPMs and binary mazes asked
+/// for, and to stdout for `maze print`.
+fn export_files(tower: &Tower, start: Location, options: &Options) -> Result<(), String> {
+    let svg_options = SvgOptions {
+        style: options.svg_style,
+        trolls: options.show_trolls,
+        ..SvgOptions::default()
+    };
+    let mut path = vec![];
+    if options.solution {
+        // The search leaves out where it started, but the line starts there.
+        path = pathfind::pathfind(tower, start);
+        if !path.is_empty() {
+            path.insert(0, start);
+        }
+    }
+    for (floor, maze) in tower.floors.iter().enumerate() {
+        let route: Vec<Posn> = path.iter().filter(|l| l.floor == floor).map(|l| l.pos).collect();
+        if let Some(ref filename) = options.svg_file {
+            write_file(&floor_filename(filename, floor, tower),
+                       svg::render(maze, &route, &svg_options).as_bytes())?;
+        }
+        if let Some(ref filename) = options.ppm_file {
+            let player = if floor == start.floor { Some(start.pos) } else { None };
+            write_file(&floor_filename(filename, floor, tower),
+                       &pnm::write_ppm(maze, &route, player, options.ppm_scale))?;
+        }
+        if options.subcommand == Subcommand::Print {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            if tower.top() > 0 {
+                writeln!(out, "Floor {}:", floor + 1).map_err(stdout_error)?;
+            }
+            write!(out, "{}", maze).map_err(stdout_error)?;
+        }
+        if let Some(ref filename) = options.binary_file {
+            write_file(&floor_filename(filename, floor, tower), &binary::write(maze))?;
+        }
     }
+    Ok(())
+}
+
+/// Sets up a game from the command line options, or prints the analysis or
+/// writes the files and returns `None` if that's all that was asked for.
+fn new_game(options: &Options) -> Result<Option<GameState>, String> {
+    // Every random decision comes from this one generator, so the seed is
+    // enough to replay a game.
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    info!("Using seed {}", seed);
+    let mut rng = GameRng::new(seed);
+
+    let (mut tower, metadata, level_player) = build_tower(options, &mut rng)?;
+    // Keep the generator's own exit unless asked for something else.
+    let placement = match options.exit_placement {
+        None if options.exits > 1 => Some(ExitPlacement::RandomBorder),
+        placement => placement,
+    };
+    let top = tower.top();
+    let start = match placement {
+        Some(placement) => {
+            exits::place_exits(&mut tower.floors[top], placement, options.exits, &mut rng)
+        }
+        None => exits::spawn_point(&tower.floors[top], &mut rng),
+    };
+    // The exits are all upstairs, so anywhere on the ground floor is far
+    // enough away from them.
+    let start = Location {
+        floor: 0,
+        pos: match level_player {
+            Some(ref player) => player.pos,
+            None if top == 0 => start,
+            None => tower.floors[0].random_floor_tile(&mut rng),
+        },
+    };
+
+    if options.analyse {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let mut steps = None;
+        for (floor, maze) in tower.floors.iter().enumerate() {
+            let analysis = analysis::analyse(maze);
+            if top > 0 {
+                writeln!(out, "Floor {}:", floor + 1).map_err(stdout_error)?;
+            }
+            writeln!(out, "{}", analysis).map_err(stdout_error)?;
+            if floor == start.floor {
+                steps = analysis.solution_lengths[start.pos];
+            }
+        }
+        if top > 0 {
+            // Each analysis only sees one floor, so the way up is counted here.
+            let path = pathfind::pathfind(&tower, start);
+            steps = if path.is_empty() { None } else { Some(path.len()) };
+        }
+        let steps = steps.map_or("no way out".to_owned(), |steps| steps.to_string());
+        writeln!(out, "From the start:   {}", steps).map_err(stdout_error)?;
+        return Ok(None);
+    }
+    // Level files place their own trolls, and exported files only have them
+    // if asked for.
+    let exporting = options.svg_file.is_some() || options.ppm_file.is_some() ||
+                    options.binary_file.is_some() ||
+                    options.subcommand != Subcommand::Play;
+    let export_only = exporting && !options.show_trolls;
+    let puzzle = metadata.as_ref().is_some_and(|m| m.ruleset == sokoban::PUZZLE_RULESET);
+    let num_trolls = match options.trolls {
+        _ if puzzle => 0,
+        Some(trolls) => trolls,
+        None if metadata.is_some() || export_only || !options.interactive => 0,
+        None => prompt_number("Enter the number of trolls:")?,
+    };
+
+    // Every floor has the same shape.
+    let topology = tower.floors[0].topology();
+    for _ in 0..num_trolls {
+        let maze = &mut tower.floors[rng.gen_range(0, top + 1)];
+        let tile = maze.random_floor_tile(&mut rng);
+        let dir = *rng.choose(topology.directions()).unwrap();
+        maze.add_troll(tile, Troll::new(dir))
+    }
+
+    let player = Player {
+        pos: start.pos,
+        dir: level_player.map_or(topology.directions()[0], |p| p.dir),
+    };
+    if exporting {
+        // Generated levels keep their trolls, whatever the pictures show.
+        let level = match options.subcommand {
+            Subcommand::Generate => {
+                Some(Level {
+                    metadata: metadata.clone(),
+                    maze: tower.floors[0].clone(),
+                    player: Some(player.clone()),
+                })
+            }
+            _ => None,
+        };
+        if !options.show_trolls {
+            for maze in &mut tower.floors {
+                maze.trolls.clear();
+            }
+        }
+        export_files(&tower, start, options)?;
+        if let Some(level) = level {
+            match options.output {
+                Some(ref filename) => write_file(filename, level.to_string().as_bytes())?,
+                None => write!(io::stdout(), "{}", level).map_err(stdout_error)?,
+            }
+        }
+        if options.subcommand == Subcommand::Solve {
+            let path = pathfind::pathfind(&tower, start);
+            if path.is_empty() {
+                return Err("There's no way out".to_owned());
+            }
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            for step in path {
+                if top > 0 {
+                    write!(out, "{} ", step.floor + 1).map_err(stdout_error)?;
+                }
+                writeln!(out, "{} {}", step.pos.row, step.pos.col).map_err(stdout_error)?;
+            }
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(GameState {
+        seed,
+        rng,
+        ticks: 0,
+        tower,
+        floor: start.floor,
+        player,
+        metadata,
+    }))
 }
 
 fn main() {
+    let args: Vec<String> = ::std::env::args().skip(1).collect();
+    let (subcommand, args) = match args.first().map(|a| &a[..]) {
+        Some("validate") => ::std::process::exit(validate_files(&args[1..])),
+        Some("help") | Some("--help") | Some("-h") => {
+            print!("{}", USAGE);
+            return;
+        }
+        Some(first) => {
+            match first.parse() {
+                Ok(subcommand) => (subcommand, &args[1..]),
+                Err(_) => (Subcommand::Play, &args[..]),
+            }
+        }
+        None => (Subcommand::Play, &args[..]),
+    };
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            ::std::process::exit(2);
+        }
+    };
+    screen::set_theme(config.theme);
+    let interactive = subcommand == Subcommand::Play && args.is_empty();
+    // The config file's settings go first, so the command line wins.
+    let args: Vec<String> = config.args.iter().chain(args).cloned().collect();
+    let options = parse_args(args.iter().cloned()).and_then(|options| {
+        let options = Options {
+            subcommand,
+            interactive,
+            ..options
+        };
+        if subcommand != Subcommand::Play &&
+           (options.campaign.is_some() || options.resume.is_some()) {
+            return Err("--campaign and --resume only work with play".to_owned());
+        }
+        if options.output.is_some() && subcommand != Subcommand::Generate {
+            return Err("--output only works with generate".to_owned());
+        }
+        if subcommand == Subcommand::Generate && options.floors > 1 {
+            return Err("Level files only have one floor, so --floors can't be used with \
+                        generate"
+                .to_owned());
+        }
+        Ok(options)
+    });
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", e);
+            ::std::process::exit(2);
+        }
+    };
+    screen::set_render_mode(options.render.unwrap_or_else(screen::default_render_mode));
+    let mut keymap = Keymap::preset(options.keys);
+    for &(ref key, command) in &config.bindings {
+        keymap.bind(key.clone(), command);
+    }
+
     let logger_config = fern::DispatchConfig {
         format: Box::new(|msg: &str, level: &log::LogLevel, _location: &log::LogLocation| {
             // This is a fairly simple format, though it's possible to do more complicated ones.
@@ -82,71 +665,282 @@ fn main() {
         panic!("Failed to initialize global logger: {}", e);
     }
 
-    let mut rng = rand::thread_rng();
-
-    print!("Enter a size of maze:");
-    ::std::io::stdout().flush().unwrap();
-    let size: usize = read!("{}\n");
-    if size < 1 {
-        println!("Too small");
+    if let Some(ref path) = options.campaign {
+        if let Err(e) = play_campaign(path, &args, &keymap) {
+            println!("{}", e);
+            ::std::process::exit(1);
+        }
         return;
     }
-    print!("Enter the number of trolls:");
-    ::std::io::stdout().flush().unwrap();
-    let num_trolls: usize = read!("{}\n");
 
-    // let mut maze = Maze::from_file("maze.txt").unwrap();
-    let mut maze = Maze::generate(size, size, &mut rng);
+    let state = match options.resume {
+        Some(ref filename) => {
+            GameState::from_file(filename)
+                .map(Some)
+                .map_err(|e| format!("Couldn't resume {}: {}", filename, e))
+        }
+        None => new_game(&options),
+    };
+    let state = match state {
+        Ok(Some(state)) => state,
+        Ok(None) => return,
+        Err(e) => {
+            println!("{}", e);
+            ::std::process::exit(1);
+        }
+    };
+    // Saving over the game we resumed means it can be carried on again.
+    let save_file = options.save_file
+        .clone()
+        .or_else(|| options.resume.clone())
+        .unwrap_or_else(|| "maze.save".to_owned());
 
-    println!("q to Quit");
+    print_key_hint(&keymap);
     println!("Maze bounds are {} by {}",
-             maze.map.len(),
-             maze.map[0].len());
+             state.tower.floors[0].map.rows(),
+             state.tower.floors[0].map.cols());
 
-    use termios::*;
+    let (mut keys, terminal) = match enter_game_screen() {
+        Ok(game_screen) => game_screen,
+        Err(e) => {
+            println!("{}", e);
+            ::std::process::exit(1);
+        }
+    };
+    let (quit_reason, state) = play(state, &mut keys, &save_file, &keymap);
+    drop(terminal);
+    print_result(quit_reason, &state);
+}
 
-    print!("\x1B[?1049h");
-    print!("\x1B[1;1H");
-    print!("\x1B[?25l");
+/// Says how to quit and get help, before the game takes over the screen.
+fn print_key_hint(keymap: &Keymap) {
+    let key = |command| {
+        keymap.keys(command).first().map_or("nothing".to_owned(), |key| key.to_string())
+    };
+    println!("{} to quit, {} for the keys", key(Command::Quit), key(Command::Help));
+}
 
-    let mut stdin = File::open("/dev/stdin").unwrap();
-    let mut termios = Termios::from_fd(stdin.as_raw_fd()).unwrap();
-    tcgetattr(stdin.as_raw_fd(), &mut termios).unwrap();
-    let mut termios_old = termios.clone();
-    termios.c_lflag = ISIG;
-    termios.c_cc[VTIME] = 0;
-    termios.c_cc[VMIN] = 1;
-    tcsetattr(stdin.as_raw_fd(), TCSAFLUSH, &termios).unwrap();
+/// Checks each maze file, printing what's wrong with it. Returns the exit
+/// status: 0 if they're all fine, 1 if any aren't, 2 if they can't be read.
+fn validate_files(files: &[String]) -> i32 {
+    if files.is_empty() {
+        println!("Usage: maze validate <file>...");
+        return 2;
+    }
+    let mut status = 0;
+    for filename in files {
+        match validate::validate_file(filename) {
+            Ok(ref problems) if problems.is_empty() => println!("{}: ok", filename),
+            Ok(problems) => {
+                for problem in problems {
+                    println!("{}: {}", filename, problem);
+                }
+                status = status.max(1);
+            }
+            Err(e) => {
+                println!("{}: {}", filename, e);
+                status = 2;
+            }
+        }
+    }
+    status
+}
 
-    for _ in 0..num_trolls {
-        let tile = maze.random_floor_tile(&mut rng);
-        maze.add_troll(tile, rng.gen())
+/// Switches to the alternate screen, hides the cursor and stops the
+/// terminal echoing or waiting for enter, until the `Terminal` is dropped.
+/// Returns the keys from stdin too.
+fn enter_game_screen() -> Result<(Keys, Terminal), String> {
+    let stdin = File::open("/dev/stdin").map_err(|e| format!("Couldn't open stdin: {}", e))?;
+    let terminal =
+        Terminal::enter().map_err(|e| format!("Couldn't take over the terminal: {}", e))?;
+    Ok((Keys::new(stdin), terminal))
+}
+
+/// Waits for a key, calling `redraw` whenever the game is carried on after
+/// being stopped with Ctrl-Z.
+fn next_key<F: FnMut()>(keys: &mut Keys, mut redraw: F) -> io::Result<Key> {
+    loop {
+        match keys.read_key() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                if terminal::take_resumed() {
+                    redraw();
+                    io::stdout().flush()?;
+                }
+            }
+            result => return result,
+        }
     }
+}
 
-    print!("{}", maze);
-    let mut player = Player {
-        pos: maze.random_floor_tile(&mut rng),
-        dir: Direction::North,
+fn print_result(quit_reason: QuitReason, state: &GameState) {
+    match quit_reason {
+        QuitReason::Quit => println!("You quit after {} ticks", state.ticks),
+        QuitReason::Eaten => println!("You were eaten after {} ticks", state.ticks),
+        QuitReason::Escaped => println!("You escaped after {} ticks", state.ticks),
+        QuitReason::Error => println!("Error!"),
+    }
+    if let Some(ref metadata) = state.metadata {
+        print!("{}", metadata.name);
+        if !metadata.author.is_empty() {
+            print!(" by {}", metadata.author);
+        }
+        print!(" ({} rules)", metadata.ruleset);
+        match metadata.par {
+            Some(par) => println!(", par {} ticks", par),
+            None => println!(),
+        }
+    }
+    println!("Seed was {}", state.seed);
+}
+
+/// Shows `message` under the maze and waits for a key. Returns false if
+/// the key was q.
+fn wait_for_key(keys: &mut Keys, row: usize, message: &str, keymap: &Keymap) -> bool {
+    move_cursor(row, 0);
+    print!("\x1B[J{}", message);
+    ::std::io::stdout().flush().unwrap();
+    // The maze isn't to hand, so after Ctrl-Z there's only the message.
+    let redraw = || print!("\x1B[1;1H\x1B[J{}", message);
+    match next_key(keys, redraw) {
+        Ok(key) => keymap.command(&key) != Some(Command::Quit),
+        Err(_) => false,
+    }
+}
+
+/// Plays the levels of a campaign in order, starting after the last one
+/// cleared. Each level is set up from the command line the game was
+/// started with, then the level's own options.
+fn play_campaign(path: &str, args: &[String], keymap: &Keymap) -> Result<(), String> {
+    let campaign = Campaign::load(path)?;
+    let count = campaign.levels.len();
+    let mut level = campaign.progress();
+    if level >= count {
+        println!("You've finished this campaign, so it's starting over");
+        level = 0;
+    }
+    // Everything but the campaign itself applies to every level.
+    let mut base = vec![];
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--campaign" {
+            rest.next();
+        } else {
+            base.push(arg.clone());
+        }
+    }
+
+    print_key_hint(keymap);
+    let save_file = parse_args(base.iter().cloned())?
+        .save_file
+        .unwrap_or_else(|| "maze.save".to_owned());
+    let (mut keys, terminal) = enter_game_screen()?;
+    let outcome = loop {
+        // There's nobody to answer questions mid-campaign, so levels get
+        // no trolls and fill the screen unless they say otherwise.
+        let defaults = vec!["--trolls".to_owned(), "0".to_owned(), "--fit".to_owned()];
+        let level_args = &campaign.levels[level];
+        let options = defaults.into_iter()
+            .chain(base.iter().cloned())
+            .chain(level_args.iter().cloned());
+        let state = parse_args(options).and_then(|mut options| {
+            // Don't play the same maze on every level.
+            if !level_args.iter().any(|a| a == "--seed") {
+                options.seed = options.seed.map(|seed| seed.wrapping_add(level as u64));
+            }
+            new_game(&options)
+        });
+        let state = match state {
+            Ok(Some(state)) => state,
+            Ok(None) => unreachable!("Campaigns don't analyse or draw levels"),
+            Err(e) => break Err(format!("Level {} of {}: {}", level + 1, count, e)),
+        };
+        info!("Starting level {} of {}", level + 1, count);
+        let (maze_rows, _) = state.tower.floors[0].bounds();
+        let message_row = maze_rows + HUD_ROWS;
+        let (quit_reason, state) = play(state, &mut keys, &save_file, keymap);
+        let carry_on = match quit_reason {
+            QuitReason::Escaped => {
+                level += 1;
+                if let Err(e) = campaign.save_progress(level) {
+                    warn!("Couldn't save the campaign progress: {}", e);
+                }
+                if level == count {
+                    break Ok((quit_reason, state));
+                }
+                let message = format!("Level {} cleared in {} ticks. Any key for level {} of \
+                                       {}, q to quit",
+                                      level,
+                                      state.ticks,
+                                      level + 1,
+                                      count);
+                wait_for_key(&mut keys, message_row, &message, keymap)
+            }
+            QuitReason::Eaten => {
+                let message = format!("You were eaten after {} ticks. Any key to try again, q \
+                                       to quit",
+                                      state.ticks);
+                wait_for_key(&mut keys, message_row, &message, keymap)
+            }
+            QuitReason::Quit | QuitReason::Error => false,
+        };
+        if !carry_on {
+            break Ok((quit_reason, state));
+        }
     };
+    drop(terminal);
 
-    player.draw();
+    let (quit_reason, state) = outcome?;
+    print_result(quit_reason, &state);
+    if level == count {
+        println!("You finished all {} levels!", count);
+    } else {
+        println!("Cleared {} of {} levels", level, count);
+    }
+    Ok(())
+}
+
+/// Runs a game until the player escapes, gets eaten or quits, starting
+/// with a fresh screen so it can follow on from another game.
+fn play(state: GameState,
+        keys: &mut Keys,
+        save_file: &str,
+        keymap: &Keymap)
+        -> (QuitReason, GameState) {
+    let GameState { seed, mut rng, mut ticks, mut tower, mut floor, mut player, metadata } = state;
+
+    // Every floor has the same shape.
+    let topology = tower.floors[0].topology();
+    let top = tower.top();
+    for (i, maze) in tower.floors.iter_mut().enumerate() {
+        maze.visible = i == floor;
+    }
+    print!("\x1B[1;1H\x1B[J{}", tower.floors[floor]);
+    player.draw(topology);
     ::std::io::stdout().flush().unwrap();
+    let (maze_rows, _) = tower.floors[0].bounds();
+    let status_row = maze_rows;
+    let path_row = maze_rows + 1;
 
     let quit_reason: QuitReason;
     info!("Starting game");
-    let mut ticks = 0;
 
     'main_loop: loop {
         ticks += 1;
-        let mut input: [u8; 64] = [0; 64];
-        let bytes = match stdin.read(&mut input) {
-            Ok(n) => n,
+        let redraw = || {
+            print!("\x1B[1;1H\x1B[J{}", tower.floors[floor]);
+            player.draw(topology);
+        };
+        let key = match next_key(keys, redraw) {
+            Ok(key) => key,
             Err(_) => {
                 quit_reason = QuitReason::Error;
                 break 'main_loop;
             }
         };
-        let command = parse_keystroke(&input[..bytes]);
+        let command = keymap.command(&key);
+        if command.is_none() {
+            debug!("No command for {:?}", key);
+        }
         let new_player = match command {
             None => continue,
             Some(Command::Quit) => {
@@ -154,25 +948,97 @@ fn main() {
                 break 'main_loop;
             }
             Some(Command::Move(dir)) => {
+                if topology.offset(dir).is_none() {
+                    // A diagonal on the square grid, or up and down on hexes.
+                    continue;
+                }
                 let mut new_player = player.clone();
-                new_player.update(dir);
+                new_player.update(dir, &tower.floors[floor]);
                 new_player
             }
+            Some(Command::Save) => {
+                let state = GameState {
+                    seed,
+                    rng,
+                    ticks,
+                    tower: tower.clone(),
+                    floor,
+                    player: player.clone(),
+                    metadata: metadata.clone(),
+                };
+                move_cursor(path_row, 0);
+                match state.save(save_file) {
+                    Ok(()) => print!("\x1B[JSaved to {}", save_file),
+                    Err(e) => print!("\x1B[JCouldn't save to {}: {}", save_file, e),
+                }
+                info!("Saved to {} after {} ticks", save_file, ticks);
+                ::std::io::stdout().flush().unwrap();
+                continue;
+            }
+            Some(Command::Help) => {
+                let show_help = || {
+                    print!("\x1B[1;1H\x1B[JKeys:\n\n");
+                    for line in keymap.help(topology) {
+                        println!("{}", line);
+                    }
+                    print!("\nPress any key to carry on");
+                };
+                show_help();
+                ::std::io::stdout().flush().unwrap();
+                if next_key(keys, show_help).is_err() {
+                    quit_reason = QuitReason::Error;
+                    break 'main_loop;
+                }
+                print!("\x1B[1;1H\x1B[J{}", tower.floors[floor]);
+                player.draw(topology);
+                ::std::io::stdout().flush().unwrap();
+                continue;
+            }
             Some(Command::Pathfind) => {
-                let path = pathfind::pathfind(&maze, player.pos);
-                move_cursor(30, 0);
-                println!("path: {:?}", path);
-                info!("path from {:?}: {:?}", player.pos, path);
+                let here = Location {
+                    floor,
+                    pos: player.pos,
+                };
+                let path = pathfind::pathfind(&tower, here);
+                // Only this floor is on screen, so stop at the stairs.
+                let steps: Vec<Posn> =
+                    path.iter().take_while(|l| l.floor == floor).map(|l| l.pos).collect();
+                let then = match path.get(steps.len()) {
+                    Some(l) if l.floor > floor => format!(", then up to floor {}", l.floor + 1),
+                    Some(l) => format!(", then down to floor {}", l.floor + 1),
+                    None => String::new(),
+                };
+                move_cursor(path_row, 0);
+                // Clear the rest of the screen, in case the last path was longer.
+                println!("\x1B[Jpath: {:?}{}", steps, then);
+                info!("path from {:?}: {:?}", here, path);
                 continue;
             }
         };
+        let maze = &mut tower.floors[floor];
         if maze.in_bounds(&new_player.pos) {
             match maze[&new_player.pos] {
-                Tile::Floor => {
+                Tile::Floor | Tile::Goal => {
                     // We've moved the player.
                     maze.redraw_tile(&player.pos);
                     player = new_player;
                 }
+                Tile::UpStairs | Tile::DownStairs => {
+                    let landing = tower.stairs_destination(Location {
+                            floor,
+                            pos: new_player.pos,
+                        })
+                        .expect("Stairs that don't go anywhere");
+                    info!("Player took the stairs to {:?}", landing);
+                    tower.floors[floor].visible = false;
+                    floor = landing.floor;
+                    tower.floors[floor].visible = true;
+                    player = new_player;
+                    player.pos = landing.pos;
+                    print!("\x1B[1;1H\x1B[J{}", tower.floors[floor]);
+                }
+                // Locked until every goal is covered.
+                Tile::Exit if maze.goals_covered() < maze.goals.len() => {}
                 Tile::Exit => {
                     quit_reason = QuitReason::Escaped;
                     break 'main_loop;
@@ -182,42 +1048,77 @@ fn main() {
                 }
             }
         }
-        let mut trolls = HashMap::new();
-        mem::swap(&mut trolls, &mut maze.trolls);
-        for (pos, mut troll) in trolls.into_iter() {
-            if pos == player.pos {
-                quit_reason = QuitReason::Eaten;
-                break 'main_loop;
-            }
-            if maze[&pos] == Tile::Wall {
-                troll.alive = false;
-            }
-            maze.redraw_tile(&pos);
-            let (pos, ate_player) = troll.update(pos, &mut maze, player.pos, &mut rng);
-            maze.add_troll(pos, troll);
-            maze.redraw_tile(&pos);
-            if ate_player {
-                quit_reason = QuitReason::Eaten;
-                break 'main_loop;
+        // Trolls keep moving on every floor, so they can follow the player
+        // up and down the stairs.
+        let mut climbing = vec![];
+        for (i, maze) in tower.floors.iter_mut().enumerate() {
+            let player_pos = if i == floor { Some(player.pos) } else { None };
+            let mut trolls = BTreeMap::new();
+            mem::swap(&mut trolls, &mut maze.trolls);
+            for (pos, mut troll) in trolls.into_iter() {
+                if Some(pos) == player_pos {
+                    quit_reason = QuitReason::Eaten;
+                    break 'main_loop;
+                }
+                if maze[&pos] == Tile::Wall {
+                    troll.alive = false;
+                }
+                maze.redraw_tile(&pos);
+                let (new_pos, ate_player) = troll.update(pos, maze, player_pos, &mut rng);
+                if new_pos != pos && maze[&new_pos].is_stairs() {
+                    climbing.push((Location {
+                                       floor: i,
+                                       pos: new_pos,
+                                   },
+                                   troll));
+                } else {
+                    maze.add_troll(new_pos, troll);
+                    maze.redraw_tile(&new_pos);
+                }
+                if ate_player {
+                    quit_reason = QuitReason::Eaten;
+                    break 'main_loop;
+                }
             }
         }
+        for (loc, troll) in climbing {
+            let landing = tower.stairs_destination(loc).expect("Stairs that don't go anywhere");
+            // Someone's in the way, so wait on the stairs.
+            let to = if tower.floors[landing.floor].trolls.contains_key(&landing.pos) {
+                loc
+            } else {
+                landing
+            };
+            tower.floors[to.floor].add_troll(to.pos, troll);
+            tower.floors[to.floor].redraw_tile(&to.pos);
+        }
 
-        player.draw();
-        move_cursor(50, 0);
-        print!("{},{}", player.pos.col, player.pos.row);
+        player.draw(topology);
+        move_cursor(status_row, 0);
+        if top > 0 {
+            print!("{},{} floor {} of {}\x1B[K",
+                   player.pos.col,
+                   player.pos.row,
+                   floor + 1,
+                   top + 1);
+        } else {
+            print!("{},{}\x1B[K", player.pos.col, player.pos.row);
+        }
+        let maze = &tower.floors[floor];
+        if !maze.goals.is_empty() {
+            print!(" goals {} of {}\x1B[K", maze.goals_covered(), maze.goals.len());
+        }
         ::std::io::stdout().flush().unwrap();
     }
     info!("Game over");
-
-    // start with it, as this fixes a broken terminal after a ctrl-c.
-    termios_old.c_lflag = ICANON | ECHO | ECHOE | ECHOK | ECHONL;
-    tcsetattr(stdin.as_raw_fd(), TCSAFLUSH, &termios_old).unwrap();
-    print!("\x1B[?1049l");
-    print!("\x1B[?25h");
-    match quit_reason {
-        QuitReason::Quit => println!("You quit after {} ticks", ticks),
-        QuitReason::Eaten => println!("You were eaten after {} ticks", ticks),
-        QuitReason::Escaped => println!("You escaped after {} ticks", ticks),
-        QuitReason::Error => println!("Error!"),
-    }
+    (quit_reason,
+     GameState {
+        seed,
+        rng,
+        ticks,
+        tower,
+        floor,
+        player,
+        metadata,
+    })
 }
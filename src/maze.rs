@@ -1,266 +1,529 @@
-use std;
 use std::fmt;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::prelude::*;
+use std::collections::BTreeMap;
+use std::io;
 use rand::Rng;
 use rand::distributions::{IndependentSample, Range};
 
 use ansi_term::Style;
-use ansi_term::ANSIStrings;
+use ansi_term::{ANSIString, ANSIStrings};
 
-use posn::{Posn, Adjacencies};
-use screen::move_cursor;
+use self::binary::BinaryError;
+use packed::TileMap;
+use pnm::PnmError;
+use posn::Posn;
+use screen::{move_cursor, render_mode, RenderMode};
 use tile::Tile;
+use topology::{Shape, Topology};
 use troll::Troll;
 use direction::Direction;
 
+pub mod analysis;
+pub mod binary;
+
+
+/// Why some text isn't a maze.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    BadCharacter(char),
+    /// A row that isn't as wide as the first one.
+    Ragged { expected: usize, found: usize },
+    EmptyRow,
+    NoRows,
+    /// A floor tile on the edge, where the player could walk off the map.
+    MissingBorder,
+    NoExit,
+    /// An exit inside the maze rather than in its outer wall.
+    ExitNotOnBorder,
+    /// Floor that no exit can be reached from.
+    Unreachable,
+    /// A level file newer than this game understands.
+    UnknownVersion(String),
+    BadHeader(String),
+    DuplicatePlayer,
+    /// A level that has to say where the player starts, but doesn't.
+    NoPlayer,
+    /// Someone facing a way the maze's shape doesn't have.
+    BadFacing(Direction),
+}
+
+/// Where parsing a maze went wrong. Lines and columns count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MazeParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseErrorKind::BadCharacter(c) => write!(f, "bad maze character {:?}", c),
+            ParseErrorKind::Ragged { expected, found } => {
+                write!(f, "row is {} tiles wide, but the first row is {}", found, expected)
+            }
+            ParseErrorKind::EmptyRow => write!(f, "empty row"),
+            ParseErrorKind::NoRows => write!(f, "there's no maze"),
+            ParseErrorKind::MissingBorder => write!(f, "gap in the outer wall"),
+            ParseErrorKind::NoExit => write!(f, "there's no exit"),
+            ParseErrorKind::ExitNotOnBorder => write!(f, "exit isn't in the outer wall"),
+            ParseErrorKind::Unreachable => write!(f, "no way from here to an exit"),
+            ParseErrorKind::UnknownVersion(ref version) => {
+                write!(f, "unknown level version '{}'", version)
+            }
+            ParseErrorKind::BadHeader(ref reason) => write!(f, "{}", reason),
+            ParseErrorKind::DuplicatePlayer => write!(f, "there's more than one player"),
+            ParseErrorKind::NoPlayer => write!(f, "there's no player"),
+            ParseErrorKind::BadFacing(dir) => {
+                write!(f, "can't face {:?} in a maze of this shape", dir)
+            }
+        }
+    }
+}
+
+impl fmt::Display for MazeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.kind)
+    }
+}
+
+/// Why a level, save or maze file couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(MazeParseError),
+    Binary(BinaryError),
+    Image(PnmError),
+    /// Asked for a level past the end of a collection, counting from 1.
+    NoLevel { number: usize, count: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => write!(f, "{}", e),
+            LoadError::Parse(ref e) => write!(f, "{}", e),
+            LoadError::Binary(ref e) => write!(f, "{}", e),
+            LoadError::Image(ref e) => write!(f, "{}", e),
+            LoadError::NoLevel { number, count } => {
+                write!(f, "there's no level {}, only 1 to {}", number, count)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl From<MazeParseError> for LoadError {
+    fn from(e: MazeParseError) -> LoadError {
+        LoadError::Parse(e)
+    }
+}
+
+impl From<BinaryError> for LoadError {
+    fn from(e: BinaryError) -> LoadError {
+        LoadError::Binary(e)
+    }
+}
+
+impl From<PnmError> for LoadError {
+    fn from(e: PnmError) -> LoadError {
+        LoadError::Image(e)
+    }
+}
+
 
 const WALL_TILE: Tile = Tile::Wall;
-const WALL_TILE_REF: &'static Tile = &WALL_TILE;
+const WALL_TILE_REF: &Tile = &WALL_TILE;
 
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Maze {
-    pub map: Vec<Vec<Tile>>,
-    pub trolls: HashMap<Posn, Troll>,
+    pub map: TileMap,
+    pub trolls: BTreeMap<Posn, Troll>,
+    pub shape: Shape,
+    /// Whether this maze is the one on screen. Changes to a maze that isn't,
+    /// like the other floors of a tower, aren't drawn.
+    pub visible: bool,
+    /// Whether the edges join up, so going off one side brings you back on
+    /// the opposite one.
+    pub wrap: bool,
+    /// Where the `Goal` tiles are, covered or not, in reading order.
+    pub goals: Vec<Posn>,
 }
 
 impl ::std::ops::Index<(usize, usize)> for Maze {
     type Output = Tile;
     fn index(&self, (row, col): (usize, usize)) -> &Tile {
-        &self.map[row][col]
+        self.map.get(row, col)
     }
 }
 
-impl<'c> ::std::ops::Index<&'c Posn> for Maze {
+impl ::std::ops::Index<&Posn> for Maze {
     type Output = Tile;
-    fn index<'a, 'b>(&'a self, p: &'b Posn) -> &'a Tile {
+    fn index(&self, p: &Posn) -> &Tile {
+        let p = &self.wrapped(*p);
         if self.in_bounds(p) {
-            &self.map[p.row as usize][p.col as usize]
+            self.map.get(p.row as usize, p.col as usize)
         } else {
             WALL_TILE_REF
         }
     }
 }
 
-impl<'c> ::std::ops::IndexMut<&'c Posn> for Maze {
-    fn index_mut<'a, 'b>(&'a mut self, p: &'b Posn) -> &'a mut Tile {
-        &mut self.map[p.row as usize][p.col as usize]
+impl fmt::Display for Maze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(render_mode()))
     }
 }
 
-impl fmt::Display for Maze {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+impl Maze {
+    pub fn topology(&self) -> &'static dyn Topology {
+        self.shape.topology()
+    }
+
+    /// Draws the maze the way `mode` says, whatever the game's render mode.
+    pub fn render(&self, mode: RenderMode) -> String {
+        let topology = self.topology();
         let mut strings = vec![];
-        let mut row = 0;
-        for line in &self.map {
-            let mut col = 0;
-            for t in line.iter() {
-                if let Some(troll) = self.trolls.get(&Posn {
-                    row: row,
-                    col: col,
-                }) {
-                    strings.push(troll.coloured());
+        for row in 0..self.map.rows() {
+            let mut screen_col = 0;
+            for col in 0..self.map.cols() {
+                let pos = Posn {
+                    row: row as i32,
+                    col: col as i32,
+                };
+                let t = self.map.get(row, col);
+                let (_, tile_col) = topology.screen_position(pos);
+                while screen_col < tile_col {
+                    strings.push(if col == 0 {
+                        Style::new().paint(" ")
+                    } else {
+                        self.gap(pos + (0, -1), mode)
+                    });
+                    screen_col += 1;
+                }
+                if let Some(troll) = self.trolls.get(&pos) {
+                    strings.push(troll.coloured_as(mode));
                 } else {
-                    strings.push(t.coloured());
+                    strings.push(t.coloured_as(mode));
                 }
-                col += 1;
+                screen_col += 1;
             }
             strings.push(Style::new().paint("\n"));
-            row += 1
         }
-        write!(f, "{}", ANSIStrings(&strings[..]))
+        ANSIStrings(&strings[..]).to_string()
     }
-}
 
+    /// What to draw between `left` and the tile to its right, when the
+    /// topology spaces tiles out on screen. Walls are joined up so they read
+    /// as solid.
+    fn gap(&self, left: Posn, mode: RenderMode) -> ANSIString<'static> {
+        let right = left + (0, 1);
+        if self[&left] == Tile::Wall && self[&right] == Tile::Wall {
+            Tile::Wall.coloured_as(mode)
+        } else {
+            Style::new().paint(" ")
+        }
+    }
 
-impl Maze {
     pub fn redraw_tile(&self, pos: &Posn) {
         assert!(self.in_bounds(pos));
-        move_cursor(pos.row as usize, pos.col as usize);
-        if self[pos] == Tile::Floor {
-            if let Some(troll) = self.trolls.get(&pos) {
+        let pos = &self.wrapped(*pos);
+        if !self.visible {
+            return;
+        }
+        let topology = self.topology();
+        let (row, col) = topology.screen_position(*pos);
+        move_cursor(row, col);
+        if self[pos].is_walkable() {
+            if let Some(troll) = self.trolls.get(pos) {
                 print!("{}", troll.coloured());
                 return;
             }
 
         }
         print!("{}", self[pos].coloured());
-    }
 
-    pub fn from_file(filename: &str) -> std::io::Result<Maze> {
-        let f = try!(File::open(filename));
-        let mut reader = BufReader::new(f);
-        let mut maze = Maze {
-            map: Vec::new(),
-            trolls: HashMap::new(),
-        };
-        loop {
-            let mut line = String::new();
-            let size = try!(reader.read_line(&mut line));
-            if size == 0 {
-                break;
+        // Walls are joined up across the gaps, so those may need redrawing.
+        for &left in &[*pos + (0, -1), *pos] {
+            let right = left + (0, 1);
+            // The gaps only sit between tiles, even in a wrapping maze.
+            if left.col < 0 || right.col >= self.map.cols() as i32 {
+                continue;
+            }
+            let (_, left_col) = topology.screen_position(left);
+            let (_, right_col) = topology.screen_position(right);
+            for gap_col in left_col + 1..right_col {
+                move_cursor(row, gap_col);
+                print!("{}", self.gap(left, render_mode()));
             }
-            let maze_line = line[..size - 1]
-                .chars()
-                .map(|c| match c {
-                    '#' => Tile::Wall,
-                    ' ' => Tile::Floor,
-                    'X' => Tile::Exit,
-                    _ => panic!("Bad maze character '{}'", c),
-                })
-                .collect();
-            maze.map.push(maze_line);
         }
-        Ok(maze)
     }
 
-    pub fn add_troll(&mut self, pos: Posn, troll: Troll) {
-        self.trolls.insert(pos, troll);
+    /// Parses a maze from text, one row per line. Either line ending works,
+    /// and blank lines at the end are ignored.
+    pub fn parse(text: &str) -> Result<Maze, MazeParseError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let maze = Maze::parse_rows(&lines, 1, Shape::Square, false, |_, _| None)?;
+        maze.check_exits(1)?;
+        Ok(maze)
     }
 
-    pub fn new(map: Vec<Vec<Tile>>) -> Maze {
-        assert!(map.len() > 0);
-        assert!(map[0].len() > 0);
-        Maze {
-            map: map,
-            trolls: HashMap::new(),
+    /// Fails with `NoExit`, pointing at `line`, if there's no way out.
+    pub fn check_exits(&self, line: usize) -> Result<(), MazeParseError> {
+        if self.exits().is_empty() {
+            return Err(MazeParseError {
+                line,
+                column: 1,
+                kind: ParseErrorKind::NoExit,
+            });
         }
+        Ok(())
     }
 
-    pub fn generate<R: Rng>(height: usize, width: usize, rng: &mut R) -> Maze {
-        let mut map = Vec::new();
-        let full_row: Vec<Tile> = vec![Tile::Wall; width * 2 + 1];
-        for _ in 0..2 * height + 1 {
-            map.push(full_row.clone());
+    /// Parses the rows of a maze that start on line `first_line` of a file.
+    ///
+    /// `glyph` is asked about any character other than a wall, floor or exit,
+    /// so richer formats can put more on the map. It returns the tile under
+    /// the glyph, or `None` if it doesn't know it either.
+    pub fn parse_rows<F>(lines: &[&str],
+                         first_line: usize,
+                         shape: Shape,
+                         wrap: bool,
+                         glyph: F)
+                         -> Result<Maze, MazeParseError>
+        where F: FnMut(char, Posn) -> Option<Tile>
+    {
+        let (maze, problems) = Maze::read_rows(lines, first_line, shape, wrap, glyph)?;
+        match problems.into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(maze),
         }
+    }
 
-        fn hunt(map: &Vec<Vec<Tile>>,
-                height: usize,
-                width: usize,
-                finished_row: usize)
-                -> Option<Posn> {
-            for row in finished_row..height {
-                for col in 0..width {
-                    if map[2 * row + 1][2 * col + 1] == Tile::Wall {
-                        return Some(Posn {
-                            row: row as i32,
-                            col: col as i32,
-                        });
-                    }
-                }
+    /// Like `parse_rows`, but carries on past bad characters, ragged rows
+    /// and gaps in the outer wall, returning them all with the maze. Bad
+    /// characters become walls, and ragged rows are cut or walled up to the
+    /// width of the first row. A wrapping maze has no outer wall, just a
+    /// seam that passages cross.
+    pub fn read_rows<F>(lines: &[&str],
+                        first_line: usize,
+                        shape: Shape,
+                        wrap: bool,
+                        mut glyph: F)
+                        -> Result<(Maze, Vec<MazeParseError>), MazeParseError>
+        where F: FnMut(char, Posn) -> Option<Tile>
+    {
+        let at = |line, column, kind| {
+            MazeParseError {
+                line,
+                column,
+                kind,
             }
-            None
-        }
-        fn lookup_center(map: &Vec<Vec<Tile>>, p: Posn) -> Tile {
-            map[(2 * p.row + 1) as usize][(2 * p.col + 1) as usize]
+        };
+        let mut lines = lines;
+        while lines.last() == Some(&"") {
+            lines = &lines[..lines.len() - 1];
         }
-        fn lookup_center_mut(map: &mut Vec<Vec<Tile>>, p: Posn) -> &mut Tile {
-            &mut map[(2 * p.row + 1) as usize][(2 * p.col + 1) as usize]
+        if lines.is_empty() {
+            return Err(at(first_line, 1, ParseErrorKind::NoRows));
         }
 
-        let mut finished_row: usize = 0;
-        // Initialize the exit.
-        map[1][0] = Tile::Exit;
-        map[1][1] = Tile::Floor;
-        info!("Generating map");
-        loop {
-            let curr = hunt(&map, height, width, finished_row);
-            if curr == None {
-                break;
+        let mut problems = vec![];
+        let mut map: Vec<Vec<Tile>> = vec![];
+        let mut goals = vec![];
+        for (i, line) in lines.iter().enumerate() {
+            let mut row = vec![];
+            for (j, c) in line.chars().enumerate() {
+                let pos = Posn {
+                    row: i as i32,
+                    col: j as i32,
+                };
+                row.push(match c {
+                    '#' => Tile::Wall,
+                    ' ' => Tile::Floor,
+                    'X' => Tile::Exit,
+                    '.' => {
+                        goals.push(pos);
+                        Tile::Goal
+                    }
+                    // A wall already on a goal.
+                    '*' => {
+                        goals.push(pos);
+                        Tile::Wall
+                    }
+                    _ => {
+                        glyph(c, pos).unwrap_or_else(|| {
+                            let kind = ParseErrorKind::BadCharacter(c);
+                            problems.push(at(first_line + i, j + 1, kind));
+                            Tile::Wall
+                        })
+                    }
+                });
             }
-            let mut curr = curr.unwrap();
-            info!("Hunt started new section at {:?}", curr);
-            finished_row = curr.row as usize;
-            let ads: Vec<Posn> = Adjacencies::new(curr)
-                .filter(|&p| {
-                    p.inside(Posn { row: 0, col: 0 },
-                             Posn {
-                                 row: height as i32,
-                                 col: width as i32,
-                             }) && lookup_center(&map, p) == Tile::Floor
-                })
-                .collect();
-            let starting_from = rng.choose(&ads).unwrap();
-            let wall = Posn {
-                row: ((2 * curr.row + 1 + 2 * starting_from.row + 1) / 2) as i32,
-                col: ((2 * curr.col + 1 + 2 * starting_from.col + 1) / 2) as i32,
-            };
-            map[wall.row as usize][wall.col as usize] = Tile::Floor;
-
-            loop {
-                assert_eq!(lookup_center(&map, curr), Tile::Wall);
-                *lookup_center_mut(&mut map, curr) = Tile::Floor;
-                let ads: Vec<Posn> = Adjacencies::new(curr)
-                    .filter(|&p| {
-                        p.inside(Posn { row: 0, col: 0 },
-                                 Posn {
-                                     row: height as i32,
-                                     col: width as i32,
-                                 }) && lookup_center(&map, p) == Tile::Wall
-                    })
-                    .collect();
-                if let Some(&next) = rng.choose(&ads) {
-                    let wall = Posn {
-                        row: ((2 * curr.row + 1 + 2 * next.row + 1) / 2) as i32,
-                        col: ((2 * curr.col + 1 + 2 * next.col + 1) / 2) as i32,
+            if row.is_empty() {
+                return Err(at(first_line + i, 1, ParseErrorKind::EmptyRow));
+            }
+            if let Some(first) = map.first() {
+                if row.len() != first.len() {
+                    let kind = ParseErrorKind::Ragged {
+                        expected: first.len(),
+                        found: row.len(),
                     };
-                    map[wall.row as usize][wall.col as usize] = Tile::Floor;
-                    curr = next;
-                } else {
-                    info!("Exhausted possibilities at {:?}", curr);
-                    break;
+                    problems.push(at(first_line + i, row.len().min(first.len()) + 1, kind));
+                    row.resize(first.len(), Tile::Wall);
                 }
+            }
+            map.push(row);
+        }
 
+        let mut maze = Maze::new(map);
+        maze.shape = shape;
+        maze.wrap = wrap;
+        maze.goals = goals;
+        let (rows, cols) = maze.bounds();
+        for row in 0..rows as i32 {
+            for col in 0..cols as i32 {
+                let pos = Posn { row, col };
+                if !wrap && maze.on_border(pos) && maze[&pos].is_walkable() {
+                    problems.push(at(first_line + row as usize,
+                                     col as usize + 1,
+                                     ParseErrorKind::MissingBorder));
+                }
             }
         }
+        Ok((maze, problems))
+    }
 
+    pub fn add_troll(&mut self, pos: Posn, troll: Troll) {
+        self.trolls.insert(pos, troll);
+    }
 
+    pub fn new(map: Vec<Vec<Tile>>) -> Maze {
+        Maze::from_map(map.into())
+    }
+
+    pub fn from_map(map: TileMap) -> Maze {
+        assert!(map.rows() > 0 && map.cols() > 0);
         Maze {
-            map: map,
-            trolls: HashMap::new(),
+            map,
+            trolls: BTreeMap::new(),
+            shape: Shape::Square,
+            visible: true,
+            wrap: false,
+            goals: vec![],
         }
     }
 
-
     //                       row    col
     pub fn bounds(&self) -> (usize, usize) {
-        (self.map.len(), self.map[0].len())
+        (self.map.rows(), self.map.cols())
     }
 
     pub fn in_bounds(&self, pos: &Posn) -> bool {
-        pos.row >= 0 && pos.row < self.map.len() as i32 && pos.col >= 0 &&
-        pos.col < self.map[0].len() as i32
+        self.wrap ||
+        pos.row >= 0 && pos.row < self.map.rows() as i32 && pos.col >= 0 &&
+        pos.col < self.map.cols() as i32
+    }
+
+    /// Brings a position back onto the map across the edges, if the maze
+    /// wraps.
+    pub fn wrapped(&self, pos: Posn) -> Posn {
+        if !self.wrap {
+            return pos;
+        }
+        let (rows, cols) = self.bounds();
+        Posn {
+            row: pos.row.rem_euclid(rows as i32),
+            col: pos.col.rem_euclid(cols as i32),
+        }
+    }
+
+    /// One step from `pos`, wrapping around the edges if the maze does.
+    pub fn step(&self, pos: Posn, dir: Direction) -> Posn {
+        self.wrapped(self.topology().step(pos, dir))
+    }
+
+    pub fn neighbours(&self, pos: Posn) -> Vec<Posn> {
+        self.topology().neighbours(pos).map(|p| self.wrapped(p)).collect()
+    }
+
+    /// Whether `pos` is on the outer wall, where exits go. A wrapping maze
+    /// only has the seam along the top and left.
+    pub fn on_border(&self, pos: Posn) -> bool {
+        let (rows, cols) = self.bounds();
+        pos.row == 0 || pos.col == 0 ||
+        !self.wrap && (pos.row == rows as i32 - 1 || pos.col == cols as i32 - 1)
+    }
+
+    /// Changes the tile at `pos`, which wraps round like indexing does.
+    pub fn set(&mut self, pos: &Posn, tile: Tile) {
+        let pos = self.wrapped(*pos);
+        self.map.set(pos.row as usize, pos.col as usize, tile);
     }
 
     pub fn push(&mut self, pos: Posn, dir: Direction) {
-        let next_tile_posn = pos + dir.numeric();
+        let next_tile_posn = self.step(pos, dir);
         if self.in_bounds(&next_tile_posn) {
             let next_tile = self[&next_tile_posn];
             if let Some(ref mut troll) = self.trolls.get_mut(&pos) {
                 troll.alive = false;
             }
-            if let Tile::Floor = next_tile {
-                self[&next_tile_posn] = Tile::Wall;
-                self[&pos] = Tile::Floor;
+            if let Tile::Floor | Tile::Goal = next_tile {
+                self.set(&next_tile_posn, Tile::Wall);
+                let uncovered = if self.goals.contains(&pos) {
+                    Tile::Goal
+                } else {
+                    Tile::Floor
+                };
+                self.set(&pos, uncovered);
                 self.redraw_tile(&pos);
                 self.redraw_tile(&next_tile_posn);
             }
         }
     }
 
+    /// How many goals have a wall on them. The exits only open once they
+    /// all do.
+    pub fn goals_covered(&self) -> usize {
+        self.goals.iter().filter(|g| self[*g] == Tile::Wall).count()
+    }
+
+    /// Row `row` of the map the way the text formats write it, without the
+    /// trolls.
+    pub fn text_row(&self, row: usize) -> String {
+        (0..self.map.cols())
+            .map(|col| match *self.map.get(row, col) {
+                Tile::Floor => ' ',
+                Tile::Wall if self.goals.contains(&Posn {
+                    row: row as i32,
+                    col: col as i32,
+                }) => '*',
+                Tile::Wall => '#',
+                Tile::Goal => '.',
+                Tile::Exit => 'X',
+                Tile::UpStairs => '<',
+                Tile::DownStairs => '>',
+            })
+            .collect()
+    }
+
+    pub fn exits(&self) -> Vec<Posn> {
+        self.map.iter().filter(|&(_, t)| t == Tile::Exit).map(|(pos, _)| pos).collect()
+    }
+
     pub fn random_floor_tile<R: Rng>(&self, rng: &mut R) -> Posn {
         let (max_row, max_col) = self.bounds();
         let mut counter = 0;
         loop {
             let row = Range::new(0, max_row as i32).ind_sample(rng);
             let col = Range::new(0, max_col as i32).ind_sample(rng);
-            let pos = Posn {
-                row: row,
-                col: col,
-            };
+            let pos = Posn { row, col };
             if self[&pos] == Tile::Floor {
                 return pos;
             }
@@ -284,3 +547,59 @@ fn test_maze_bounds() {
     assert_eq!(maze.in_bounds(&Posn { row: 1, col: 10 }), false);
     assert_eq!(maze.in_bounds(&Posn { row: 1, col: 3 }), true);
 }
+
+#[test]
+fn test_wrapped_steps() {
+    use direction::Direction::*;
+    use tile::Tile::*;
+    let row = vec![Wall, Floor, Floor, Floor];
+    let mut maze = Maze::new(vec![row.clone(), row.clone(), row.clone()]);
+    let corner = Posn { row: 0, col: 0 };
+    assert_eq!(maze.step(corner, North), Posn { row: -1, col: 0 });
+    assert!(!maze.in_bounds(&maze.step(corner, North)));
+
+    maze.wrap = true;
+    assert_eq!(maze.step(corner, North), Posn { row: 2, col: 0 });
+    assert_eq!(maze.step(corner, West), Posn { row: 0, col: 3 });
+    assert!(maze.in_bounds(&Posn { row: 10, col: -10 }));
+    assert_eq!(maze[&Posn { row: 1, col: 5 }], Floor);
+
+    // Pushing a wall off the left edge puts it on the right.
+    maze.visible = false;
+    maze.push(Posn { row: 1, col: 0 }, West);
+    assert_eq!(maze[&Posn { row: 1, col: 3 }], Wall);
+    assert_eq!(maze[&Posn { row: 1, col: 0 }], Floor);
+}
+
+#[test]
+fn test_parse_errors() {
+    let parse_error = |text: &str| Maze::parse(text).unwrap_err();
+    let at = |line, column, kind| {
+        MazeParseError {
+            line,
+            column,
+            kind,
+        }
+    };
+
+    let maze = Maze::parse("#####\nX   #\n#####").unwrap();
+    assert_eq!(maze.bounds(), (3, 5));
+    assert_eq!(Maze::parse("#####\r\nX   #\r\n#####\r\n\r\n").unwrap(), maze);
+
+    assert_eq!(parse_error("#####\nX ? #\n#####\n"),
+               at(2, 3, ParseErrorKind::BadCharacter('?')));
+    assert_eq!(parse_error("#####\nX  #\n#####\n"),
+               at(2,
+                  5,
+                  ParseErrorKind::Ragged {
+                      expected: 5,
+                      found: 4,
+                  }));
+    assert_eq!(parse_error("#####\n\nX   #\n#####\n"),
+               at(2, 1, ParseErrorKind::EmptyRow));
+    assert_eq!(parse_error("\n\n"), at(1, 1, ParseErrorKind::NoRows));
+    assert_eq!(parse_error("#####\nX    \n#####\n"),
+               at(2, 5, ParseErrorKind::MissingBorder));
+    assert_eq!(parse_error("#####\n#   #\n#####\n"),
+               at(1, 1, ParseErrorKind::NoExit));
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+
+use grid::Grid;
+use maze::Maze;
+use pathfind::distances;
+use posn::Posn;
+use tile::Tile;
+
+/// Structural statistics for a maze, counted over its floor tiles.
+#[derive(Debug)]
+pub struct Analysis {
+    pub floors: usize,
+    /// Floor tiles with only one way out.
+    pub dead_ends: usize,
+    /// Floor tiles with exactly two ways out.
+    pub corridors: usize,
+    /// Floor tiles with three or more ways out.
+    pub junctions: usize,
+    /// The longest shortest path between any two open tiles.
+    pub diameter: usize,
+    /// Whether `diameter` is exact. It's only a lower bound on big mazes
+    /// with loops.
+    pub diameter_exact: bool,
+    /// Steps from each tile to the nearest exit.
+    pub solution_lengths: Grid<Option<usize>>,
+    pub longest_solution: usize,
+    pub mean_solution: f64,
+    /// Floor tiles with no route to an exit.
+    pub unreachable: usize,
+    /// The average number of new ways on from a tile, not counting the way
+    /// you came in.
+    pub branching_factor: f64,
+    /// Mean solution length, weighted up by the share of floor tiles that
+    /// ask the player to make a choice: the dead ends and junctions anywhere
+    /// in the maze, not just on the way out.
+    pub difficulty: f64,
+}
+
+/// Mazes with loops and more open tiles than this get an estimated
+/// diameter, as the exact one takes a search from every tile.
+const EXACT_DIAMETER_LIMIT: usize = 2000;
+
+fn open_neighbours(maze: &Maze, pos: Posn) -> Vec<Posn> {
+    maze.neighbours(pos).into_iter().filter(|p| maze[p] != Tile::Wall).collect()
+}
+
+/// The diameter, and whether it's exact.
+///
+/// Each part of a perfect maze is a tree, where searching from anywhere
+/// and then from the farthest tile found gives the exact diameter in two
+/// searches. With loops that's only a lower bound, so small mazes get a
+/// search from every tile instead.
+fn diameter(maze: &Maze, open: &[Posn]) -> (usize, bool) {
+    let (rows, cols) = maze.bounds();
+    let mut seen = Grid::new(vec![vec![false; cols]; rows]);
+    let (mut parts, mut sweep) = (0, 0);
+    for &pos in open {
+        if seen[pos] {
+            continue;
+        }
+        parts += 1;
+        let dist = distances(maze, &[pos]);
+        let mut farthest = (0, pos);
+        for &other in open {
+            if let Some(d) = dist[other] {
+                seen[other] = true;
+                farthest = farthest.max((d, other));
+            }
+        }
+        let dist = distances(maze, &[farthest.1]);
+        sweep = open.iter().filter_map(|&other| dist[other]).fold(sweep, usize::max);
+    }
+
+    let edges = open.iter().map(|&pos| open_neighbours(maze, pos).len()).sum::<usize>() / 2;
+    if edges + parts == open.len() {
+        (sweep, true)
+    } else if open.len() <= EXACT_DIAMETER_LIMIT {
+        let mut diameter = 0;
+        for &pos in open {
+            let dist = distances(maze, &[pos]);
+            diameter = open.iter().filter_map(|&other| dist[other]).fold(diameter, usize::max);
+        }
+        (diameter, true)
+    } else {
+        (sweep, false)
+    }
+}
+
+pub fn analyse(maze: &Maze) -> Analysis {
+    let mut floors = vec![];
+    let mut open = vec![];
+    for (pos, t) in maze.map.iter() {
+        if t != Tile::Wall {
+            open.push(pos);
+        }
+        if t == Tile::Floor || t == Tile::Goal {
+            floors.push(pos);
+        }
+    }
+
+    let (mut dead_ends, mut corridors, mut junctions) = (0, 0, 0);
+    let mut onward_choices = 0;
+    for &pos in &floors {
+        let degree = open_neighbours(maze, pos).len();
+        match degree {
+            0 => {}
+            1 => dead_ends += 1,
+            2 => corridors += 1,
+            _ => junctions += 1,
+        }
+        onward_choices += degree.saturating_sub(1);
+    }
+
+    let (diameter, diameter_exact) = diameter(maze, &open);
+
+    let solution_lengths = distances(maze, &maze.exits());
+    let mut unreachable = 0;
+    let mut longest_solution = 0;
+    let mut total_solution = 0;
+    for &pos in &floors {
+        match solution_lengths[pos] {
+            Some(d) => {
+                longest_solution = longest_solution.max(d);
+                total_solution += d;
+            }
+            None => unreachable += 1,
+        }
+    }
+
+    let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+    let mean_solution = ratio(total_solution, floors.len() - unreachable);
+    let branching_factor = ratio(onward_choices, floors.len());
+    let decisions = ratio(dead_ends + junctions, floors.len());
+    Analysis {
+        floors: floors.len(),
+        dead_ends,
+        corridors,
+        junctions,
+        diameter,
+        diameter_exact,
+        solution_lengths,
+        longest_solution,
+        mean_solution,
+        unreachable,
+        branching_factor,
+        difficulty: mean_solution * (1.0 + decisions),
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Floor tiles:      {}", self.floors)?;
+        writeln!(f, "Dead ends:        {}", self.dead_ends)?;
+        writeln!(f, "Corridors:        {}", self.corridors)?;
+        writeln!(f, "Junctions:        {}", self.junctions)?;
+        let at_least = if self.diameter_exact { "" } else { " (at least)" };
+        writeln!(f, "Diameter:         {}{}", self.diameter, at_least)?;
+        writeln!(f, "Longest solution: {}", self.longest_solution)?;
+        writeln!(f, "Mean solution:    {:.1}", self.mean_solution)?;
+        writeln!(f, "Unreachable:      {}", self.unreachable)?;
+        writeln!(f, "Branching factor: {:.2}", self.branching_factor)?;
+        write!(f, "Difficulty:       {:.1}", self.difficulty)
+    }
+}
+
+#[test]
+fn test_analyse_small_maze() {
+    use tile::Tile::*;
+    // #####
+    // X   #
+    // # # #
+    // #   #
+    // #####
+    let maze = Maze::new(vec![vec![Wall, Wall, Wall, Wall, Wall],
+                              vec![Exit, Floor, Floor, Floor, Wall],
+                              vec![Wall, Wall, Floor, Wall, Wall],
+                              vec![Wall, Floor, Floor, Floor, Wall],
+                              vec![Wall, Wall, Wall, Wall, Wall]]);
+    let analysis = analyse(&maze);
+    assert_eq!(analysis.floors, 7);
+    assert_eq!(analysis.dead_ends, 3);
+    assert_eq!(analysis.corridors, 2);
+    assert_eq!(analysis.junctions, 2);
+    assert_eq!(analysis.diameter, 5);
+    assert!(analysis.diameter_exact);
+    assert_eq!(analysis.longest_solution, 5);
+    assert_eq!(analysis.solution_lengths[Posn { row: 3, col: 1 }], Some(5));
+    assert_eq!(analysis.unreachable, 0);
+    assert_eq!(analysis.mean_solution, 23.0 / 7.0);
+    assert_eq!(analysis.branching_factor, 6.0 / 7.0);
+}
+
+#[test]
+fn test_diameter() {
+    use braid::braid;
+    use generator::Algorithm;
+    use rng::GameRng;
+    use topology::Shape;
+
+    let open = |maze: &Maze| -> Vec<Posn> {
+        let (rows, cols) = maze.bounds();
+        (0..rows as i32)
+            .flat_map(|row| (0..cols as i32).map(move |col| Posn { row, col }))
+            .filter(|p| maze[p] != Tile::Wall)
+            .collect()
+    };
+    let every_tile = |maze: &Maze| {
+        let open = open(maze);
+        open.iter()
+            .map(|&pos| {
+                let dist = distances(maze, &[pos]);
+                open.iter().filter_map(|&other| dist[other]).max().unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    };
+    let mut rng = GameRng::new(7);
+
+    // Two searches are enough for a perfect maze, even a big one.
+    let small = Algorithm::RecursiveBacktracker.generate(Shape::Square, 8, 8, false, &mut rng);
+    assert_eq!(diameter(&small, &open(&small)), (every_tile(&small), true));
+    let big = Algorithm::RecursiveBacktracker.generate(Shape::Square, 60, 60, false, &mut rng);
+    assert!(open(&big).len() > EXACT_DIAMETER_LIMIT);
+    assert!(diameter(&big, &open(&big)).1);
+
+    // Loops mean searching from every tile, or an estimate on big mazes.
+    let mut small = small;
+    braid(&mut small, 100, &mut rng);
+    assert_eq!(diameter(&small, &open(&small)), (every_tile(&small), true));
+    let mut big = big;
+    braid(&mut big, 100, &mut rng);
+    let (estimate, exact) = diameter(&big, &open(&big));
+    assert!(!exact && estimate > 0);
+}
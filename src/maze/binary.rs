@@ -0,0 +1,264 @@
+use std::fmt;
+
+use maze::{Maze, MazeParseError, ParseErrorKind};
+use packed::{self, TileMap, WordsError};
+use posn::Posn;
+use tile::Tile;
+use topology::Shape;
+
+const MAGIC: &[u8] = b"maze-bin";
+const VERSION: u8 = 1;
+
+/// Why some bytes aren't a binary maze.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BinaryError {
+    NotBinary,
+    CutShort,
+    UnknownVersion(u8),
+    BadShape(u8),
+    BadWrap(u8),
+    /// There aren't exactly enough bytes for the tiles.
+    WrongSize {
+        rows: usize,
+        cols: usize,
+        expected: usize,
+        found: usize,
+    },
+    BadTiles(WordsError),
+    /// A goal where the map has no goal tile, or wall on one.
+    NoGoal(Posn),
+    /// Something wrong with the map itself. There are no lines in a binary
+    /// maze, so the line and column are its row and column.
+    Map(MazeParseError),
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BinaryError::NotBinary => write!(f, "not a binary maze"),
+            BinaryError::CutShort => write!(f, "the file is cut short"),
+            BinaryError::UnknownVersion(version) => {
+                write!(f, "unknown binary maze version {}", version)
+            }
+            BinaryError::BadShape(n) => write!(f, "unknown shape {}", n),
+            BinaryError::BadWrap(n) => write!(f, "bad wrap flag {}", n),
+            BinaryError::WrongSize { rows, cols, expected, found } => {
+                write!(f,
+                       "expected {} bytes of tiles for {} by {}, not {}",
+                       expected,
+                       rows,
+                       cols,
+                       found)
+            }
+            BinaryError::BadTiles(ref e) => write!(f, "{}", e),
+            BinaryError::NoGoal(pos) => {
+                write!(f, "there's no goal at row {}, column {}", pos.row + 1, pos.col + 1)
+            }
+            BinaryError::Map(ref e) if e.kind == ParseErrorKind::NoExit => write!(f, "{}", e.kind),
+            BinaryError::Map(ref e) => {
+                write!(f, "row {}, column {}: {}", e.line, e.column, e.kind)
+            }
+        }
+    }
+}
+
+/// Whether `bytes` start like a binary maze.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Writes `maze` in the binary format, which keeps the tiles packed just as
+/// they are in memory, so huge mazes save and load quickly:
+///
+/// ```text
+/// maze-bin        8 bytes
+/// version         1 byte, 1
+/// shape           1 byte, 0 for square or 1 for hex
+/// wrap            1 byte, 0 or 1
+/// rows, cols      u32 each
+/// goals           a u32 count, then a u32 row and column for each
+/// tiles           u64 words of 21 three bit tiles, in reading order
+/// ```
+///
+/// Numbers are little endian. Trolls aren't saved.
+pub fn write(maze: &Maze) -> Vec<u8> {
+    let (rows, cols) = maze.bounds();
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.push(match maze.shape {
+        Shape::Square => 0,
+        Shape::Hex => 1,
+    });
+    bytes.push(maze.wrap as u8);
+    for n in &[rows, cols, maze.goals.len()] {
+        bytes.extend_from_slice(&(*n as u32).to_le_bytes());
+    }
+    for goal in &maze.goals {
+        bytes.extend_from_slice(&(goal.row as u32).to_le_bytes());
+        bytes.extend_from_slice(&(goal.col as u32).to_le_bytes());
+    }
+    for word in maze.map.words() {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+/// Takes numbers off the front of a binary maze.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BinaryError> {
+        if self.bytes.len() < n {
+            return Err(BinaryError::CutShort);
+        }
+        let (front, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(front)
+    }
+
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, BinaryError> {
+        let mut n = [0; 4];
+        n.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(n) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64, BinaryError> {
+        let mut n = [0; 8];
+        n.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(n))
+    }
+}
+
+/// Loads a maze written by `write`, checking it as strictly as a text one.
+pub fn read(bytes: &[u8]) -> Result<Maze, BinaryError> {
+    if !is_binary(bytes) {
+        return Err(BinaryError::NotBinary);
+    }
+    let mut reader = Reader { bytes: &bytes[MAGIC.len()..] };
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(BinaryError::UnknownVersion(version));
+    }
+    let shape = match reader.byte()? {
+        0 => Shape::Square,
+        1 => Shape::Hex,
+        n => return Err(BinaryError::BadShape(n)),
+    };
+    let wrap = match reader.byte()? {
+        0 => false,
+        1 => true,
+        n => return Err(BinaryError::BadWrap(n)),
+    };
+    let rows = reader.u32()?;
+    let cols = reader.u32()?;
+    let error = |pos: Posn, kind: ParseErrorKind| {
+        Err(BinaryError::Map(MazeParseError {
+            line: pos.row as usize + 1,
+            column: pos.col as usize + 1,
+            kind,
+        }))
+    };
+    if rows == 0 || cols == 0 {
+        return error(Posn { row: 0, col: 0 }, ParseErrorKind::NoRows);
+    }
+    let mut goals = vec![];
+    for _ in 0..reader.u32()? {
+        goals.push(Posn {
+            row: reader.u32()? as i32,
+            col: reader.u32()? as i32,
+        });
+    }
+    // Check the size against what's left before allocating anything.
+    let words = packed::word_count(rows * cols);
+    if reader.bytes.len() != words * 8 {
+        return Err(BinaryError::WrongSize {
+            rows,
+            cols,
+            expected: words * 8,
+            found: reader.bytes.len(),
+        });
+    }
+    let words = (0..words).map(|_| reader.u64()).collect::<Result<_, _>>()?;
+
+    let map = TileMap::from_words(rows, cols, words).map_err(BinaryError::BadTiles)?;
+    let mut maze = Maze::from_map(map);
+    maze.shape = shape;
+    maze.wrap = wrap;
+    maze.goals = goals;
+    // Checked against the size itself, as a wrapping maze would bring any
+    // position round onto the map.
+    let size = Posn {
+        row: rows as i32,
+        col: cols as i32,
+    };
+    for &goal in &maze.goals {
+        if !goal.inside(Posn { row: 0, col: 0 }, size) ||
+           maze[&goal] != Tile::Goal && maze[&goal] != Tile::Wall {
+            return Err(BinaryError::NoGoal(goal));
+        }
+    }
+    // Passages cross the seam of a wrapping maze, so only the others have
+    // an outer wall to check.
+    if !wrap {
+        let border = (0..rows)
+            .flat_map(|row| vec![(row, 0), (row, cols - 1)])
+            .chain((0..cols).flat_map(|col| vec![(0, col), (rows - 1, col)]));
+        for (row, col) in border {
+            let pos = Posn {
+                row: row as i32,
+                col: col as i32,
+            };
+            if maze[&pos].is_walkable() {
+                return error(pos, ParseErrorKind::MissingBorder);
+            }
+        }
+    }
+    maze.check_exits(1).map_err(BinaryError::Map)?;
+    Ok(maze)
+}
+
+#[test]
+fn test_binary_round_trip() {
+    use generator::Algorithm;
+    use rng::GameRng;
+
+    let mut maze = Maze::parse("#######\n#  .  X\n# #*# #\n#######\n").unwrap();
+    maze.shape = Shape::Hex;
+    let bytes = write(&maze);
+    assert!(is_binary(&bytes));
+    assert_eq!(bytes.len(), 8 + 3 + 12 + 16 + 2 * 8);
+    assert_eq!(read(&bytes), Ok(maze.clone()));
+
+    assert!(read(&bytes[..bytes.len() - 1]).unwrap_err().to_string().contains("bytes of tiles"));
+    assert_eq!(read(&bytes[..20]), Err(BinaryError::CutShort));
+    let mut holed = maze.clone();
+    holed.set(&Posn { row: 3, col: 2 }, Tile::Floor);
+    let error = read(&write(&holed)).unwrap_err();
+    assert_eq!(error,
+               BinaryError::Map(MazeParseError {
+                   line: 4,
+                   column: 3,
+                   kind: ParseErrorKind::MissingBorder,
+               }));
+    assert_eq!(error.to_string(), "row 4, column 3: gap in the outer wall");
+
+    // Passages cross the seam of a wrapping maze.
+    let mut rng = GameRng::new(5);
+    let wrapped = Algorithm::RecursiveBacktracker.generate(Shape::Square, 6, 6, true, &mut rng);
+    assert!(wrapped.map.iter().any(|(pos, t)| t == Tile::Floor && wrapped.on_border(pos)));
+    assert_eq!(read(&write(&wrapped)), Ok(wrapped.clone()));
+    // Even though it would wrap round onto a wall.
+    let mut stray = wrapped;
+    let far = Posn {
+        row: 2 * stray.map.rows() as i32,
+        col: 0,
+    };
+    stray.goals.push(far);
+    assert_eq!(read(&write(&stray)), Err(BinaryError::NoGoal(far)));
+}
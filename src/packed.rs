@@ -0,0 +1,171 @@
+use std::fmt;
+
+use posn::Posn;
+use tile::Tile;
+
+/// How many bits each tile takes.
+const BITS: usize = 3;
+/// Tiles never straddle two words, so the top bit of each is left over.
+const PER_WORD: usize = 64 / BITS;
+const MASK: u64 = (1 << BITS) - 1;
+
+/// How many words it takes to pack `tiles` tiles.
+pub fn word_count(tiles: usize) -> usize {
+    tiles.div_ceil(PER_WORD)
+}
+
+/// Why some words aren't a map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WordsError {
+    WrongCount { expected: usize, found: usize },
+    /// A code that isn't any tile.
+    BadTile { code: u8, pos: Posn },
+}
+
+impl fmt::Display for WordsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WordsError::WrongCount { expected, found } => {
+                write!(f, "expected {} words of tiles, not {}", expected, found)
+            }
+            WordsError::BadTile { code, pos } => {
+                write!(f, "bad tile {} at row {}, column {}", code, pos.row + 1, pos.col + 1)
+            }
+        }
+    }
+}
+
+/// A rectangle of tiles packed three bits to a tile, 21 to a word, in one
+/// flat allocation. A 5000 by 5000 maze fits in under 10MB.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileMap {
+    rows: usize,
+    cols: usize,
+    words: Vec<u64>,
+}
+
+impl TileMap {
+    pub fn new(rows: usize, cols: usize, fill: Tile) -> TileMap {
+        let word = (0..PER_WORD).fold(0, |word, i| word | (fill.code() as u64) << (i * BITS));
+        TileMap {
+            rows,
+            cols,
+            words: vec![word; word_count(rows * cols)],
+        }
+    }
+
+    /// Rebuilds a map from what `words` gave, checking every tile is real.
+    pub fn from_words(rows: usize, cols: usize, words: Vec<u64>) -> Result<TileMap, WordsError> {
+        if words.len() != word_count(rows * cols) {
+            return Err(WordsError::WrongCount {
+                expected: word_count(rows * cols),
+                found: words.len(),
+            });
+        }
+        let map = TileMap { rows, cols, words };
+        for i in 0..rows * cols {
+            let (word, shift) = (i / PER_WORD, i % PER_WORD * BITS);
+            let code = (map.words[word] >> shift & MASK) as u8;
+            if Tile::from_code(code).is_none() {
+                let pos = Posn {
+                    row: (i / cols) as i32,
+                    col: (i % cols) as i32,
+                };
+                return Err(WordsError::BadTile { code, pos });
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn locate(&self, row: usize, col: usize) -> (usize, usize) {
+        assert!(row < self.rows && col < self.cols,
+                "({}, {}) is off a {} by {} map",
+                row,
+                col,
+                self.rows,
+                self.cols);
+        let i = row * self.cols + col;
+        (i / PER_WORD, i % PER_WORD * BITS)
+    }
+
+    /// The tile at (`row`, `col`), as a reference so `Index` can hand it out.
+    pub fn get(&self, row: usize, col: usize) -> &'static Tile {
+        let (word, shift) = self.locate(row, col);
+        Tile::from_code((self.words[word] >> shift & MASK) as u8).expect("Corrupt tile map")
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, tile: Tile) {
+        let (word, shift) = self.locate(row, col);
+        self.words[word] = self.words[word] & !(MASK << shift) | (tile.code() as u64) << shift;
+    }
+
+    /// Every tile with its position, in reading order.
+    pub fn iter(&self) -> impl Iterator<Item = (Posn, Tile)> + '_ {
+        (0..self.rows).flat_map(move |row| {
+            (0..self.cols).map(move |col| {
+                (Posn {
+                     row: row as i32,
+                     col: col as i32,
+                 },
+                 *self.get(row, col))
+            })
+        })
+    }
+}
+
+impl From<Vec<Vec<Tile>>> for TileMap {
+    fn from(rows: Vec<Vec<Tile>>) -> TileMap {
+        let cols = rows.first().map_or(0, |row| row.len());
+        let mut map = TileMap::new(rows.len(), cols, Tile::Wall);
+        for (r, row) in rows.iter().enumerate() {
+            assert_eq!(row.len(), cols, "Ragged row {}", r);
+            for (c, &tile) in row.iter().enumerate() {
+                map.set(r, c, tile);
+            }
+        }
+        map
+    }
+}
+
+#[test]
+fn test_tile_map() {
+    let mut map = TileMap::new(7, 9, Tile::Wall);
+    assert_eq!(map.words().len(), 3);
+    assert!(map.iter().all(|(_, t)| t == Tile::Wall));
+
+    // Either side of the first word boundary, and the very last tile.
+    map.set(2, 2, Tile::Goal);
+    map.set(2, 3, Tile::Exit);
+    map.set(6, 8, Tile::DownStairs);
+    map.set(2, 3, Tile::Floor);
+    assert_eq!(*map.get(2, 2), Tile::Goal);
+    assert_eq!(*map.get(2, 3), Tile::Floor);
+    assert_eq!(*map.get(2, 4), Tile::Wall);
+    assert_eq!(*map.get(6, 8), Tile::DownStairs);
+    assert_eq!(map.iter().filter(|&(_, t)| t != Tile::Wall).count(), 3);
+
+    let copy = TileMap::from_words(7, 9, map.words().to_vec()).unwrap();
+    assert_eq!(copy, map);
+    assert_eq!(TileMap::from_words(7, 9, vec![0; 2]),
+               Err(WordsError::WrongCount {
+                   expected: 3,
+                   found: 2,
+               }));
+    assert_eq!(TileMap::from_words(1, 1, vec![7]),
+               Err(WordsError::BadTile {
+                   code: 7,
+                   pos: Posn { row: 0, col: 0 },
+               }));
+}
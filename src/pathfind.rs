@@ -1,122 +1,79 @@
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{HashMap, VecDeque};
 
 
 use grid::Grid;
-use direction::Direction;
-use direction::Direction::*;
 use maze::Maze;
 use posn::Posn;
 use tile::Tile;
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-struct Node {
-    cost: Option<i32>,
-    from: Direction,
-}
-
-impl Node {
-    fn new() -> Node {
-        Node {
-            cost: None,
-            from: Direction::North,
-        }
-    }
-}
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-struct HeapEntry {
-    cost: i32,
-    pos: Posn,
-    from: Direction,
-}
-
-impl PartialOrd for HeapEntry {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
-    }
-}
-
-impl Ord for HeapEntry {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.cost.cmp(&self.cost)
-    }
-}
-
-
-pub fn pathfind(maze: &Maze, player: Posn) -> Vec<Posn> {
-    let mut search_nodes = Grid::new(maze.map
-        .iter()
-        .map(|row| {
-            row.iter()
-                .map(|_| Node::new())
-                .collect()
-        })
-        .collect());
-
-    let mut heap = BinaryHeap::new();
-    search_nodes[player].cost = None;
-
-    heap.push(HeapEntry {
-        cost: 0,
-        pos: player,
-        from: North,
-    });
-    let mut exit_pos: Option<Posn> = None;
-
-    while let Some(HeapEntry { cost, pos, from }) = heap.pop() {
-        trace!("Considering cell {:?}", pos);
-        assert!(maze.in_bounds(&pos));
-        if maze[&pos] == Tile::Exit {
-            trace!("Found the exit at {:?}", pos);
-            search_nodes[pos].from = from;
-            exit_pos = Some(pos);
-            break;
-        }
-
-        let node = search_nodes[pos];
-        if let Some(old_cost) = node.cost {
-            if old_cost <= cost {
-                trace!("Old cost of {:?} was better than new cost {:?}",
-                       old_cost,
-                       cost);
-                continue;
+use tower::{Location, Tower};
+
+/// Breadth first search for the nearest exit, taking the stairs as well,
+/// so it finds the way out from any floor of a tower.
+///
+/// Stepping onto stairs puts you on the other floor straight away, so the
+/// path lists where you land rather than the stairs themselves.
+pub fn pathfind(tower: &Tower, from: Location) -> Vec<Location> {
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::new();
+    came_from.insert(from, from);
+    queue.push_back(from);
+    while let Some(loc) = queue.pop_front() {
+        let maze = &tower.floors[loc.floor];
+        if maze[&loc.pos] == Tile::Exit {
+            trace!("Found the exit at {:?}", loc);
+            let mut path = vec![];
+            let mut curr = loc;
+            while curr != from {
+                path.push(curr);
+                curr = came_from[&curr];
             }
+            path.reverse();
+            return path;
         }
-        search_nodes[pos].cost = Some(cost);
-        search_nodes[pos].from = from;
-
-        for dir in &[North, East, South, West] {
-            let next_pos = pos + dir.numeric();
+        for next_pos in maze.neighbours(loc.pos) {
             // Also handles out of bounds.
             if maze[&next_pos] == Tile::Wall {
                 continue;
             }
-            assert!(maze.in_bounds(&next_pos));
-            heap.push(HeapEntry {
+            let next = Location {
+                floor: loc.floor,
                 pos: next_pos,
-                cost: cost + 1,
-                from: dir.flip(),
-            });
+            };
+            let next = tower.stairs_destination(next).unwrap_or(next);
+            if came_from.contains_key(&next) {
+                continue;
+            }
+            came_from.insert(next, loc);
+            queue.push_back(next);
         }
     }
+    warn!("Couldn't find a path");
+    vec![]
+}
 
-    if exit_pos == None {
-        warn!("Couldn't find a path");
-        return vec![];
+/// Breadth first search out from every source at once, giving the number of
+/// steps from each open tile to the nearest source, or `None` if it can't be
+/// reached.
+pub fn distances(maze: &Maze, sources: &[Posn]) -> Grid<Option<usize>> {
+    let (rows, cols) = maze.bounds();
+    let mut dist = Grid::new(vec![vec![None; cols]; rows]);
+    let mut queue = VecDeque::new();
+    for &source in sources {
+        dist[source] = Some(0);
+        queue.push_back(source);
     }
-    let mut path = vec![];
-    let mut curr_pos = exit_pos.unwrap();
-    trace!("Path:");
-    while curr_pos != player {
-        path.push(curr_pos);
-        trace!("{:?}", curr_pos);
-        let next_dir = search_nodes[curr_pos].from;
-        trace!("Stepping to the {:?}", next_dir);
-        curr_pos = curr_pos + next_dir.numeric();
+    while let Some(pos) = queue.pop_front() {
+        let cost = dist[pos].unwrap();
+        for next_pos in maze.neighbours(pos) {
+            // Also handles out of bounds.
+            if maze[&next_pos] == Tile::Wall || dist[next_pos].is_some() {
+                continue;
+            }
+            dist[next_pos] = Some(cost + 1);
+            queue.push_back(next_pos);
+        }
     }
-    path.reverse();
-    path
+    dist
 }
 
 // fn shortest_path(adj_list: &Vec<Vec<Edge>>, start: usize, goal: usize) -> Option<usize> {
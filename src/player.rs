@@ -1,10 +1,12 @@
 use std::fmt;
 
-use ansi_term::Colour::Green;
+use ansi_term::Style;
 
 use direction::Direction;
+use maze::Maze;
 use posn::Posn;
-use screen::move_cursor;
+use screen::{move_cursor, paint, render_mode, theme, RenderMode};
+use topology::Topology;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Player {
@@ -13,16 +15,17 @@ pub struct Player {
 }
 
 impl Player {
-    pub fn draw(&self) {
+    pub fn draw(&self, topology: &dyn Topology) {
         assert!(self.pos.row >= 0);
         assert!(self.pos.col >= 0);
 
-        move_cursor(self.pos.row as usize, self.pos.col as usize);
+        let (row, col) = topology.screen_position(self.pos);
+        move_cursor(row, col);
         print!("{}", self);
     }
-    pub fn update(&mut self, dir: Direction) {
+    pub fn update(&mut self, dir: Direction, maze: &Maze) {
         if dir == self.dir {
-            self.pos = self.pos + dir.numeric();
+            self.pos = maze.step(self.pos, dir);
         } else {
             self.dir = dir
         }
@@ -32,6 +35,12 @@ impl Player {
 
 impl fmt::Display for Player {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", Green.paint(self.dir.unicode()))
+        match render_mode() {
+            RenderMode::Ascii => write!(f, "@"),
+            _ => {
+                let glyph = self.dir.unicode();
+                write!(f, "{}", paint(theme().player.normal(), Style::new().bold(), glyph))
+            }
+        }
     }
 }
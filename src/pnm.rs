@@ -0,0 +1,317 @@
+use std::fmt;
+
+use maze::{Maze, MazeParseError};
+use posn::Posn;
+use tile::Tile;
+use topology::Shape;
+
+/// An RGB colour, with 255 as full brightness.
+type Rgb = (u8, u8, u8);
+
+const WALL: Rgb = (0, 0, 0);
+const FLOOR: Rgb = (255, 255, 255);
+/// Also what marks the exits in a PPM being loaded.
+const EXIT: Rgb = (0, 0, 255);
+const STAIRS: Rgb = (255, 200, 0);
+const GOAL: Rgb = (200, 0, 200);
+const TROLL: Rgb = (255, 0, 0);
+const DEAD_TROLL: Rgb = (128, 0, 0);
+const PLAYER: Rgb = (0, 160, 0);
+const ROUTE: Rgb = (150, 230, 150);
+
+/// Why an image isn't a maze.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PnmError {
+    NotNetpbm,
+    /// The header or a plain raster is missing a number, such as the width.
+    Expected(&'static str),
+    /// A plain PBM pixel that isn't a 0 or 1.
+    BadBit,
+    CutShort,
+    Empty,
+    BadMaxval(u32),
+    /// Something wrong with the maze drawn. The line and column are the
+    /// pixel's row and column.
+    Map(MazeParseError),
+}
+
+impl fmt::Display for PnmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PnmError::NotNetpbm => write!(f, "not a Netpbm image"),
+            PnmError::Expected(what) => write!(f, "expected the {}", what),
+            PnmError::BadBit => write!(f, "expected a 0 or 1 pixel"),
+            PnmError::CutShort => write!(f, "the image is cut short"),
+            PnmError::Empty => write!(f, "the image is empty"),
+            PnmError::BadMaxval(maxval) => write!(f, "bad maximum value {}", maxval),
+            PnmError::Map(ref e) => {
+                write!(f, "pixel row {}, column {}: {}", e.line, e.column, e.kind)
+            }
+        }
+    }
+}
+
+/// Whether `bytes` start like a Netpbm image, `P1` to `P6`.
+pub fn is_netpbm(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] == b'P' && (b'1'..=b'6').contains(&bytes[1])
+}
+
+/// Reads the whitespace separated numbers of a Netpbm header or plain
+/// raster, skipping `#` comments.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.bytes.get(self.at) {
+            if b == b'#' {
+                while self.bytes.get(self.at).is_some_and(|&b| b != b'\n') {
+                    self.at += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.at += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self, what: &'static str) -> Result<u32, PnmError> {
+        self.skip_space();
+        let start = self.at;
+        while self.bytes.get(self.at).is_some_and(|b| b.is_ascii_digit()) {
+            self.at += 1;
+        }
+        String::from_utf8_lossy(&self.bytes[start..self.at])
+            .parse()
+            .map_err(|_| PnmError::Expected(what))
+    }
+
+    /// Plain PBM digits don't need anything between them.
+    fn bit(&mut self) -> Result<bool, PnmError> {
+        self.skip_space();
+        let bit = match self.bytes.get(self.at) {
+            Some(&b'0') => false,
+            Some(&b'1') => true,
+            _ => return Err(PnmError::BadBit),
+        };
+        self.at += 1;
+        Ok(bit)
+    }
+
+    fn byte(&mut self) -> Result<u8, PnmError> {
+        let byte = *self.bytes.get(self.at).ok_or(PnmError::CutShort)?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    /// A raw sample, two bytes wide if `maxval` needs them.
+    fn sample(&mut self, maxval: u32) -> Result<u32, PnmError> {
+        if maxval < 256 {
+            Ok(self.byte()? as u32)
+        } else {
+            Ok((self.byte()? as u32) << 8 | self.byte()? as u32)
+        }
+    }
+}
+
+/// Loads a maze drawn as a PBM, PGM or PPM image, one pixel per tile, plain
+/// or raw.
+///
+/// Dark pixels are walls and light ones are floor. The exits are marked in
+/// mid grey (127 or 128 out of 255) in a PGM, or pure blue in a PPM. A PBM
+/// has only black and white, so any gap in its outer wall is an exit.
+pub fn parse(bytes: &[u8]) -> Result<Maze, PnmError> {
+    if !is_netpbm(bytes) {
+        return Err(PnmError::NotNetpbm);
+    }
+    let kind = bytes[1] - b'0';
+    let mut reader = Reader { bytes, at: 2 };
+    let width = reader.number("width")? as usize;
+    let height = reader.number("height")? as usize;
+    let maxval = if kind == 1 || kind == 4 {
+        1
+    } else {
+        reader.number("maximum value")?
+    };
+    if width == 0 || height == 0 {
+        return Err(PnmError::Empty);
+    }
+    if maxval == 0 || maxval > 65535 {
+        return Err(PnmError::BadMaxval(maxval));
+    }
+    // Exactly one whitespace character comes before a raw raster.
+    reader.at += 1;
+
+    let dark = |grey: u32| grey * 2 < maxval;
+    let mut lines = vec![];
+    for _ in 0..height {
+        let mut line = String::new();
+        for col in 0..width {
+            let tile = match kind {
+                1 => if reader.bit()? { '#' } else { ' ' },
+                4 => {
+                    let byte = reader.bytes
+                        .get(reader.at + col / 8)
+                        .ok_or(PnmError::CutShort)?;
+                    if byte & (0x80 >> (col % 8)) != 0 { '#' } else { ' ' }
+                }
+                2 | 5 => {
+                    let grey = if kind == 2 {
+                        reader.number("pixel")?
+                    } else {
+                        reader.sample(maxval)?
+                    };
+                    if (grey * 2).abs_diff(maxval) <= 1 {
+                        'X'
+                    } else if dark(grey) {
+                        '#'
+                    } else {
+                        ' '
+                    }
+                }
+                _ => {
+                    let mut rgb = [0; 3];
+                    for sample in &mut rgb {
+                        *sample = if kind == 3 {
+                            reader.number("pixel")?
+                        } else {
+                            reader.sample(maxval)?
+                        };
+                    }
+                    let [r, g, b] = rgb;
+                    if (r, g, b) == (0, 0, maxval) {
+                        'X'
+                    } else if dark((299 * r + 587 * g + 114 * b) / 1000) {
+                        '#'
+                    } else {
+                        ' '
+                    }
+                }
+            };
+            line.push(tile);
+        }
+        if kind == 4 {
+            reader.at += width.div_ceil(8);
+        }
+        lines.push(line);
+    }
+
+    if kind == 1 || kind == 4 {
+        let last = height - 1;
+        for (row, line) in lines.iter_mut().enumerate() {
+            *line = line.char_indices()
+                .map(|(col, c)| {
+                    let border = row == 0 || row == last || col == 0 || col == width - 1;
+                    if border && c == ' ' { 'X' } else { c }
+                })
+                .collect();
+        }
+    }
+
+    let lines: Vec<&str> = lines.iter().map(|l| &l[..]).collect();
+    Maze::parse_rows(&lines, 1, Shape::Square, false, |_, _| None)
+        .and_then(|maze| maze.check_exits(1).map(|_| maze))
+        .map_err(PnmError::Map)
+}
+
+/// Draws `maze` as a raw PPM, `scale` pixels to a tile. `route` is coloured
+/// in under the player and the trolls.
+pub fn write_ppm(maze: &Maze, route: &[Posn], player: Option<Posn>, scale: usize) -> Vec<u8> {
+    let (rows, cols) = maze.bounds();
+    // Hex rows are shifted half a tile further right each time down.
+    let shift = |row: usize| match maze.shape {
+        Shape::Square => 0,
+        Shape::Hex => row * scale / 2,
+    };
+    let width = cols * scale + shift(rows - 1);
+    let height = rows * scale;
+    let mut pixels = vec![FLOOR; width * height];
+    let mut paint = |pos: Posn, colour: Rgb| {
+        let (row, col) = (pos.row as usize, pos.col as usize);
+        let left = col * scale + shift(row);
+        for y in row * scale..(row + 1) * scale {
+            for x in left..left + scale {
+                pixels[y * width + x] = colour;
+            }
+        }
+    };
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let pos = Posn {
+                row: row as i32,
+                col: col as i32,
+            };
+            paint(pos,
+                  match maze[&pos] {
+                      Tile::Floor => FLOOR,
+                      Tile::Wall => WALL,
+                      Tile::Exit => EXIT,
+                      Tile::UpStairs | Tile::DownStairs => STAIRS,
+                      Tile::Goal => GOAL,
+                  });
+        }
+    }
+    for &pos in route {
+        if maze[&pos] == Tile::Floor {
+            paint(pos, ROUTE);
+        }
+    }
+    for (&pos, troll) in &maze.trolls {
+        paint(pos, if troll.alive { TROLL } else { DEAD_TROLL });
+    }
+    if let Some(pos) = player {
+        paint(pos, PLAYER);
+    }
+
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for (r, g, b) in pixels {
+        ppm.extend_from_slice(&[r, g, b]);
+    }
+    ppm
+}
+
+#[test]
+fn test_netpbm() {
+    use maze::ParseErrorKind;
+
+    let maze = Maze::parse("#####\n#   X\n#####\n").unwrap();
+
+    let plain_pbm = b"P1\n# a comment\n5 3\n11111\n10000\n11111\n";
+    assert_eq!(parse(plain_pbm), Ok(maze.clone()));
+    let raw_pbm = b"P4 5 3\n\xf8\x80\xf8";
+    assert_eq!(parse(raw_pbm), Ok(maze.clone()));
+    let plain_pgm = b"P2 5 3 15\n0 0 0 0 0\n0 15 12 15 8\n0 0 0 0 0\n";
+    assert_eq!(parse(plain_pgm), Ok(maze.clone()));
+    let error = parse(b"P2 5 3 15\n0 0 0 0 0\n0 15 12 15 15\n0 0 0 0 0\n").unwrap_err();
+    assert_eq!(error,
+               PnmError::Map(MazeParseError {
+                   line: 2,
+                   column: 5,
+                   kind: ParseErrorKind::MissingBorder,
+               }));
+    assert_eq!(error.to_string(), "pixel row 2, column 5: gap in the outer wall");
+    assert_eq!(parse(b"P5 5 3 255\n\0\0"), Err(PnmError::CutShort));
+    assert_eq!(parse(b"P2 5 three 15\n"), Err(PnmError::Expected("height")));
+
+    // One pixel per tile reads back in.
+    let ppm = write_ppm(&maze, &[], None, 1);
+    assert_eq!(parse(&ppm), Ok(maze.clone()));
+
+    let route = [Posn { row: 1, col: 2 }, Posn { row: 1, col: 3 }];
+    let ppm = write_ppm(&maze, &route, Some(Posn { row: 1, col: 1 }), 2);
+    let header = b"P6\n10 6\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(ppm.len(), header.len() + 10 * 6 * 3);
+    let pixel = |x: usize, y: usize| {
+        let at = header.len() + (y * 10 + x) * 3;
+        (ppm[at], ppm[at + 1], ppm[at + 2])
+    };
+    assert_eq!(pixel(0, 0), WALL);
+    assert_eq!(pixel(3, 3), PLAYER);
+    assert_eq!(pixel(5, 2), ROUTE);
+    assert_eq!(pixel(9, 3), EXIT);
+}
@@ -1,6 +1,6 @@
 use direction::Direction;
 
-#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Posn {
     pub row: i32,
     pub col: i32,
@@ -53,45 +53,6 @@ impl ::std::ops::Add<(i32, i32)> for Posn {
     }
 }
 
-pub struct Adjacencies {
-    pos: Posn,
-    dir: Direction,
-    done: bool,
-}
-
-impl Adjacencies {
-    pub fn new(pos: Posn) -> Adjacencies {
-        Adjacencies {
-            pos: pos,
-            dir: Direction::North,
-            done: false,
-        }
-    }
-}
-
-impl Iterator for Adjacencies {
-    type Item = Posn;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.done {
-            return None;
-        }
-        let next_pos = self.pos + self.dir.numeric();
-        let dir = {
-            match self.dir {
-                Direction::North => Direction::East,
-                Direction::East => Direction::South,
-                Direction::South => Direction::West,
-                Direction::West => {
-                    self.done = true;
-                    Direction::North
-                }
-            }
-        };
-        self.dir = dir;
-        Some(next_pos)
-    }
-}
-
 #[test]
 fn test_posn_add() {
     let a = Posn { row: 1, col: 1 };
@@ -106,16 +67,17 @@ fn test_posn_add() {
 
 #[test]
 fn test_iter_adjacencies() {
+    use topology::{Square, Topology};
     let p = Posn { row: 1, col: 1 };
-    let ads: Vec<Posn> = Adjacencies::new(p).collect();
+    let ads: Vec<Posn> = Square.neighbours(p).collect();
     assert_eq!(ads,
                vec![
-              p + Direction::North.numeric(),
-              p + Direction::East.numeric(),
-              p + Direction::South.numeric(),
-              p + Direction::West.numeric(),
+              Square.step(p, Direction::North),
+              Square.step(p, Direction::East),
+              Square.step(p, Direction::South),
+              Square.step(p, Direction::West),
               ]);
-    let ads: Vec<Posn> = Adjacencies::new(Posn { row: 0, col: 1 })
+    let ads: Vec<Posn> = Square.neighbours(Posn { row: 0, col: 1 })
         .filter(|&p| p.row >= 0 && p.row < 2 && p.col >= 0 && p.col < 2)
         .collect();
     assert_eq!(ads,
@@ -123,7 +85,7 @@ fn test_iter_adjacencies() {
                Posn{ row: 1, col: 1},
                Posn{ row: 0, col: 0},
                ]);
-    let ads: Vec<Posn> = Adjacencies::new(Posn { row: 0, col: 1 })
+    let ads: Vec<Posn> = Square.neighbours(Posn { row: 0, col: 1 })
         .filter(|&p| p.inside(Posn { row: 0, col: 0 }, Posn { row: 2, col: 2 }))
         .collect();
     assert_eq!(ads,
@@ -137,9 +99,10 @@ fn test_iter_adjacencies() {
 #[test]
 fn test_dir_to() {
     use direction::Direction::*;
+    use topology::{Square, Topology};
     let p = Posn { row: 1, col: 1 };
     for dir in &[North, South, East, West] {
-        assert_eq!(p.direction_to(p + dir.numeric()), *dir);
+        assert_eq!(p.direction_to(Square.step(p, *dir)), *dir);
     }
     assert_eq!(p.direction_to(p), North);
 }
@@ -0,0 +1,63 @@
+use rand::{Rng, SeedableRng};
+
+/// The random number generator every game decision is drawn from.
+///
+/// This is SplitMix64: small, fast, and its whole state is a single `u64`,
+/// so a game can be reproduced from the seed it started with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng { state: seed }
+    }
+
+    /// Where the generator is up to. `GameRng::new` with this carries on
+    /// from the same point.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl SeedableRng<u64> for GameRng {
+    fn reseed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+
+    fn from_seed(seed: u64) -> GameRng {
+        GameRng::new(seed)
+    }
+}
+
+#[test]
+fn test_same_seed_same_game() {
+    use generator::Algorithm;
+    use topology::Shape;
+
+    let mut a = GameRng::new(1234);
+    let mut b = GameRng::new(1234);
+    let maze_a = Algorithm::HuntAndKill.generate(Shape::Square, 10, 10, false, &mut a);
+    let maze_b = Algorithm::HuntAndKill.generate(Shape::Square, 10, 10, false, &mut b);
+    assert_eq!(maze_a, maze_b);
+    assert_eq!(maze_a.random_floor_tile(&mut a), maze_b.random_floor_tile(&mut b));
+    assert_eq!(a.next_u64(), b.next_u64());
+
+    let mut c = GameRng::new(1235);
+    assert!(Algorithm::HuntAndKill.generate(Shape::Square, 10, 10, false, &mut c) != maze_a);
+}
@@ -1,6 +1,176 @@
+use std::env;
+use std::mem;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ansi_term::{ANSIString, Colour, Style};
+use ansi_term::Colour::{Blue, Green, Purple, Red, Yellow};
+use libc;
+
+/// How the maze and everything in it is drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    Colour,
+    /// No colours, only bold and dim to tell things apart.
+    Mono,
+    /// No escape codes at all, and ASCII letters for the player and trolls.
+    Ascii,
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<RenderMode, String> {
+        match s {
+            "colour" | "color" => Ok(RenderMode::Colour),
+            "mono" => Ok(RenderMode::Mono),
+            "ascii" => Ok(RenderMode::Ascii),
+            _ => Err(format!("Unknown render mode '{}', expected colour, mono or ascii", s)),
+        }
+    }
+}
+
+static RENDER_MODE: AtomicUsize = AtomicUsize::new(0);
+
+pub fn render_mode() -> RenderMode {
+    match RENDER_MODE.load(Ordering::Relaxed) {
+        0 => RenderMode::Colour,
+        1 => RenderMode::Mono,
+        _ => RenderMode::Ascii,
+    }
+}
+
+pub fn set_render_mode(mode: RenderMode) {
+    RENDER_MODE.store(mode as usize, Ordering::Relaxed);
+}
+
+/// The colours things are drawn in, when there's colour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub wall: Colour,
+    pub exit: Colour,
+    pub stairs: Colour,
+    pub goal: Colour,
+    pub player: Colour,
+    pub troll: Colour,
+    pub dead_troll: Colour,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        CLASSIC
+    }
+}
+
+const CLASSIC: Theme = Theme {
+    wall: Red,
+    exit: Blue,
+    stairs: Yellow,
+    goal: Purple,
+    player: Green,
+    troll: Blue,
+    dead_troll: Red,
+};
+
+static THEME: RwLock<Theme> = RwLock::new(CLASSIC);
+
+pub fn theme() -> Theme {
+    *THEME.read().unwrap()
+}
+
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap() = theme;
+}
+
+/// Reads a colour by name, or as a number from the 256 colour palette.
+pub fn parse_colour(s: &str) -> Result<Colour, String> {
+    use ansi_term::Colour::*;
+    Ok(match s {
+        "black" => Black,
+        "red" => Red,
+        "green" => Green,
+        "yellow" => Yellow,
+        "blue" => Blue,
+        "purple" => Purple,
+        "cyan" => Cyan,
+        "white" => White,
+        _ => {
+            Fixed(s.parse().map_err(|_| {
+                format!("Unknown colour '{}', expected black, red, green, yellow, blue, \
+                         purple, cyan, white or 0 to 255",
+                        s)
+            })?)
+        }
+    })
+}
+
+/// ASCII when stdout isn't a terminal, no colour if `NO_COLOR` is set, and
+/// colour otherwise.
+pub fn default_render_mode() -> RenderMode {
+    if unsafe { libc::isatty(libc::STDOUT_FILENO) } == 0 {
+        RenderMode::Ascii
+    } else if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        RenderMode::Mono
+    } else {
+        RenderMode::Colour
+    }
+}
+
+/// Styles `text` with `colour` or `mono`, whichever the render mode wants,
+/// or not at all for ASCII.
+pub fn paint(colour: Style, mono: Style, text: &'static str) -> ANSIString<'static> {
+    paint_as(render_mode(), colour, mono, text)
+}
+
+/// Like `paint`, but for `mode` rather than the render mode set for the
+/// whole game.
+pub fn paint_as(mode: RenderMode,
+                colour: Style,
+                mono: Style,
+                text: &'static str)
+                -> ANSIString<'static> {
+    match mode {
+        RenderMode::Colour => colour.paint(text),
+        RenderMode::Mono => mono.paint(text),
+        RenderMode::Ascii => Style::new().paint(text),
+    }
+}
 
 // The screen in 1 indexed, not zero indexed.
 pub fn move_cursor(row: usize, col: usize) {
     // TODO add a camera/viewport
     print!("\x1B[{row};{col}f", row = 1 + row, col = 1 + col);
 }
+
+/// Asks the terminal on stdout for its size, as (rows, cols).
+///
+/// Returns `None` when stdout isn't a terminal.
+pub fn terminal_size() -> Option<(usize, usize)> {
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0 {
+            return None;
+        }
+        if size.ws_row == 0 || size.ws_col == 0 {
+            return None;
+        }
+        Some((size.ws_row as usize, size.ws_col as usize))
+    }
+}
+
+#[test]
+fn test_render_modes() {
+    use direction::Direction;
+    use maze::Maze;
+    use posn::Posn;
+    use troll::Troll;
+
+    assert_eq!("color".parse(), Ok(RenderMode::Colour));
+    assert!("sepia".parse::<RenderMode>().is_err());
+
+    let mut maze = Maze::parse("#####\n#   X\n#####\n").unwrap();
+    maze.add_troll(Posn { row: 1, col: 2 }, Troll::new(Direction::East));
+    assert_eq!(maze.render(RenderMode::Ascii), "#####\n# T X\n#####\n");
+    assert_eq!(maze.render(RenderMode::Mono), "#####\n# ▶ \x1B[1mX\x1B[0m\n#####\n");
+    assert!(maze.render(RenderMode::Colour).contains("\x1B[31m#"));
+}
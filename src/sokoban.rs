@@ -0,0 +1,244 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+use level::{split_header, Level, Metadata};
+use maze::{LoadError, Maze, MazeParseError, ParseErrorKind};
+use player::Player;
+use posn::Posn;
+use topology::Shape;
+
+/// The ruleset of imported Sokoban levels: no trolls, and the exit only
+/// opens once there's a wall on every goal.
+pub const PUZZLE_RULESET: &str = "puzzle";
+
+/// Whether `filename` looks like a collection of Sokoban levels.
+pub fn is_collection(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(".xsb")
+}
+
+fn is_map_line(line: &str) -> bool {
+    line.contains('#') && line.chars().all(|c| "#@+$*. -_".contains(c))
+}
+
+/// Loads level `number`, counting from 1, of an XSB file.
+pub fn from_file(filename: &str, number: usize) -> Result<Level, LoadError> {
+    let mut text = String::new();
+    File::open(filename)?.read_to_string(&mut text)?;
+    let levels = parse(&text)?;
+    let count = levels.len();
+    levels.into_iter()
+        .nth(number.wrapping_sub(1))
+        .ok_or(LoadError::NoLevel { number, count })
+}
+
+/// Reads the levels of an XSB file, the usual plain text Sokoban format.
+///
+/// Boxes (`$`) become walls to push, and goals (`.`) become `Goal` tiles.
+/// `*` is a box on a goal and `+` the player on one. Everything outside the
+/// level's walls is filled in, and an exit is cut in the outer wall, as
+/// close to the top left as the player can reach. `Title:` and `Author:`
+/// lines after a map name it, and anything else between maps is ignored.
+pub fn parse(text: &str) -> Result<Vec<Level>, MazeParseError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut levels: Vec<Level> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        if is_map_line(lines[i]) {
+            let start = i;
+            while i < lines.len() && is_map_line(lines[i]) {
+                i += 1;
+            }
+            levels.push(parse_level(&lines[start..i], start + 1, levels.len() + 1)?);
+            continue;
+        }
+        if let (Some(level), Ok((key, value))) = (levels.last_mut(), split_header(lines[i])) {
+            if let Some(ref mut metadata) = level.metadata {
+                match &key.to_lowercase()[..] {
+                    "title" => metadata.name = value.to_owned(),
+                    "author" => metadata.author = value.to_owned(),
+                    _ => {}
+                }
+            }
+        }
+        i += 1;
+    }
+    if levels.is_empty() {
+        return Err(MazeParseError {
+            line: 1,
+            column: 1,
+            kind: ParseErrorKind::NoRows,
+        });
+    }
+    Ok(levels)
+}
+
+fn parse_level(lines: &[&str], first_line: usize, number: usize) -> Result<Level, MazeParseError> {
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let mut grid: Vec<Vec<char>> = lines.iter()
+        .map(|l| {
+            let mut row: Vec<char> = l.chars().collect();
+            row.resize(width, ' ');
+            row
+        })
+        .collect();
+    let (rows, cols) = (grid.len(), width);
+    let neighbours = |(r, c): (usize, usize)| {
+        let mut next = vec![];
+        if r > 0 {
+            next.push((r - 1, c));
+        }
+        if c > 0 {
+            next.push((r, c - 1));
+        }
+        if r + 1 < rows {
+            next.push((r + 1, c));
+        }
+        if c + 1 < cols {
+            next.push((r, c + 1));
+        }
+        next
+    };
+    let error = |(r, c): (usize, usize), kind| {
+        Err(MazeParseError {
+            line: first_line + r,
+            column: c + 1,
+            kind,
+        })
+    };
+
+    let mut players = vec![];
+    for (r, row) in grid.iter().enumerate() {
+        for (c, &ch) in row.iter().enumerate() {
+            if ch == '@' || ch == '+' {
+                players.push((r, c));
+            }
+        }
+    }
+    let player = match players[..] {
+        [] => return error((0, 0), ParseErrorKind::NoPlayer),
+        [player] => player,
+        [_, second, ..] => return error(second, ParseErrorKind::DuplicatePlayer),
+    };
+
+    // Flood in from the edges to find what's outside the walls, and out from
+    // the player to find what's inside. Boxes can be pushed, so they don't
+    // get in the way.
+    let flood = |starts: Vec<(usize, usize)>, open: &dyn Fn(char) -> bool| {
+        let mut seen = vec![vec![false; cols]; rows];
+        let mut stack = starts;
+        while let Some((r, c)) = stack.pop() {
+            if seen[r][c] || !open(grid[r][c]) {
+                continue;
+            }
+            seen[r][c] = true;
+            stack.extend(neighbours((r, c)));
+        }
+        seen
+    };
+    let edges = (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (r, c)))
+        .filter(|&(r, c)| r == 0 || c == 0 || r == rows - 1 || c == cols - 1)
+        .collect();
+    let outside = flood(edges, &|ch| ch == ' ' || ch == '-' || ch == '_');
+    let inside = flood(vec![player], &|ch| ch != '#');
+
+    let exit = (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (r, c)))
+        .find(|&(r, c)| {
+            let next = neighbours((r, c));
+            grid[r][c] == '#' && next.iter().any(|&(nr, nc)| inside[nr][nc]) &&
+            (next.len() < 4 || next.iter().any(|&(nr, nc)| outside[nr][nc]))
+        });
+    match exit {
+        Some((r, c)) => grid[r][c] = 'X',
+        None => return error((0, 0), ParseErrorKind::NoExit),
+    }
+
+    let text: Vec<String> = grid.iter()
+        .enumerate()
+        .map(|(r, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(c, &ch)| match ch {
+                    _ if outside[r][c] => '#',
+                    '$' => '#',
+                    '@' | '-' | '_' => ' ',
+                    '+' => '.',
+                    ch => ch,
+                })
+                .collect()
+        })
+        .collect();
+    let text: Vec<&str> = text.iter().map(|l| &l[..]).collect();
+    let maze = Maze::parse_rows(&text, first_line, Shape::Square, false, |_, _| None)?;
+
+    Ok(Level {
+        metadata: Some(Metadata {
+            name: format!("Level {}", number),
+            ruleset: PUZZLE_RULESET.to_owned(),
+            ..Metadata::default()
+        }),
+        player: Some(Player {
+            pos: Posn {
+                row: player.0 as i32,
+                col: player.1 as i32,
+            },
+            dir: maze.topology().directions()[0],
+        }),
+        maze,
+    })
+}
+
+#[test]
+fn test_parse_xsb() {
+    use direction::Direction;
+    use tile::Tile;
+
+    let text = ["; 1",
+                "",
+                "  #####",
+                "###  #",
+                "#.@$ #",
+                "#### #",
+                "   #*#",
+                "   ###",
+                "Title: First",
+                "Author: Someone",
+                "",
+                "; 2",
+                "#####",
+                "#+$ #",
+                "#####"]
+        .join("\n");
+    let levels = parse(&text).unwrap();
+    assert_eq!(levels.len(), 2);
+    let first = &levels[0];
+    let metadata = first.metadata.as_ref().unwrap();
+    assert_eq!((&metadata.name[..], &metadata.author[..]), ("First", "Someone"));
+    assert_eq!(metadata.ruleset, PUZZLE_RULESET);
+    assert_eq!(first.player.as_ref().unwrap().pos, Posn { row: 2, col: 2 });
+
+    let mut maze = first.maze.clone();
+    assert_eq!(maze.bounds(), (6, 7));
+    // Outside the walls is filled in, and the exit is in the top wall.
+    assert_eq!(maze[&Posn { row: 5, col: 0 }], Tile::Wall);
+    assert_eq!(maze.exits(), vec![Posn { row: 0, col: 3 }]);
+    assert_eq!(maze.goals, vec![Posn { row: 2, col: 1 }, Posn { row: 4, col: 4 }]);
+    assert_eq!(maze.goals_covered(), 1);
+
+    // Push the box onto the goal, and off it again.
+    maze.push(Posn { row: 2, col: 3 }, Direction::West);
+    maze.push(Posn { row: 2, col: 2 }, Direction::West);
+    assert_eq!(maze.goals_covered(), 2);
+    maze.push(Posn { row: 2, col: 1 }, Direction::East);
+    assert_eq!(maze[&Posn { row: 2, col: 1 }], Tile::Goal);
+    assert_eq!(maze.goals_covered(), 1);
+
+    let second = &levels[1];
+    assert_eq!(second.metadata.as_ref().unwrap().name, "Level 2");
+    assert_eq!(second.maze[&second.player.as_ref().unwrap().pos], Tile::Goal);
+
+    assert_eq!(parse("#####\n# $.#\n#####\n").unwrap_err().kind,
+               ParseErrorKind::NoPlayer);
+    assert_eq!(parse("no levels here\n").unwrap_err().kind, ParseErrorKind::NoRows);
+}
@@ -0,0 +1,253 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use direction::Direction;
+use maze::Maze;
+use posn::Posn;
+use tile::Tile;
+use topology::Shape;
+
+/// How walls are drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WallStyle {
+    /// Every wall tile filled in, like the game draws them.
+    Cells,
+    /// Lines joining neighbouring wall tiles, like a maze on paper.
+    Lines,
+}
+
+impl FromStr for WallStyle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<WallStyle, String> {
+        match s {
+            "cells" => Ok(WallStyle::Cells),
+            "lines" => Ok(WallStyle::Lines),
+            _ => Err(format!("Unknown wall style '{}', expected cells or lines", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    /// The distance between neighbouring tiles, in pixels.
+    pub tile_size: f64,
+    pub style: WallStyle,
+    /// Draws the trolls as arrows pointing the way they face.
+    pub trolls: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            tile_size: 10.0,
+            style: WallStyle::Cells,
+            trolls: false,
+        }
+    }
+}
+
+/// Hexagons are packed closer together going down than across.
+const HEX_ROW_HEIGHT: f64 = 0.866;
+
+/// The centre of a tile, in pixels.
+fn centre(maze: &Maze, pos: Posn, size: f64) -> (f64, f64) {
+    let (row, col) = (pos.row as f64, pos.col as f64);
+    match maze.shape {
+        Shape::Square => ((col + 0.5) * size, (row + 0.5) * size),
+        Shape::Hex => ((col + row / 2.0 + 0.5) * size, (row * HEX_ROW_HEIGHT + 0.5) * size),
+    }
+}
+
+/// The outline of a tile, as an SVG element with the given fill.
+fn tile(maze: &Maze, pos: Posn, size: f64, fill: &str) -> String {
+    let (x, y) = centre(maze, pos, size);
+    match maze.shape {
+        Shape::Square => {
+            format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                    x - size / 2.0,
+                    y - size / 2.0,
+                    size,
+                    size,
+                    fill)
+        }
+        Shape::Hex => {
+            // Pointy topped, touching its neighbours.
+            let radius = size / 3f64.sqrt();
+            let points: Vec<String> = (0..6)
+                .map(|i| {
+                    let angle = (60.0 * i as f64 + 30.0).to_radians();
+                    format!("{:.1},{:.1}", x + radius * angle.cos(), y - radius * angle.sin())
+                })
+                .collect();
+            format!("<polygon points=\"{}\" fill=\"{}\"/>", points.join(" "), fill)
+        }
+    }
+}
+
+/// A triangle on `pos` pointing in `dir`.
+fn arrow(maze: &Maze, pos: Posn, dir: Direction, size: f64, fill: &str) -> String {
+    let (x, y) = centre(maze, pos, size);
+    let (to_x, to_y) = centre(maze, maze.topology().step(pos, dir), size);
+    let length = ((to_x - x).powi(2) + (to_y - y).powi(2)).sqrt();
+    let (ux, uy) = ((to_x - x) / length, (to_y - y) / length);
+    let r = size * 0.4;
+    format!("<polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{}\"/>",
+            x + ux * r,
+            y + uy * r,
+            x - ux * r - uy * r * 0.7,
+            y - uy * r + ux * r * 0.7,
+            x - ux * r + uy * r * 0.7,
+            y - uy * r - ux * r * 0.7,
+            fill)
+}
+
+/// Draws `maze` as an SVG document, with the exits in blue, goals as purple
+/// dots and `route`, if it isn't empty, as a green line from the first tile
+/// on it.
+pub fn render(maze: &Maze, route: &[Posn], options: &SvgOptions) -> String {
+    let size = options.tile_size;
+    let (rows, cols) = maze.bounds();
+    let (width, height) = match maze.shape {
+        Shape::Square => (cols as f64 * size, rows as f64 * size),
+        Shape::Hex => {
+            ((cols as f64 + (rows - 1) as f64 / 2.0) * size,
+             ((rows - 1) as f64 * HEX_ROW_HEIGHT + 1.0) * size)
+        }
+    };
+    let contains = |p: &Posn| {
+        p.row >= 0 && p.col >= 0 && p.row < rows as i32 && p.col < cols as i32
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(svg,
+                     "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+                      viewBox=\"0 0 {:.1} {:.1}\">",
+                     width.ceil(),
+                     height.ceil(),
+                     width,
+                     height);
+    let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+
+    match options.style {
+        WallStyle::Cells => svg.push_str("<g>\n"),
+        WallStyle::Lines => {
+            let _ = writeln!(svg,
+                             "<g stroke=\"black\" stroke-width=\"{:.1}\" \
+                              stroke-linecap=\"round\">",
+                             size / 3.0);
+        }
+    }
+    let (mut exits, mut goals) = (vec![], vec![]);
+    for row in 0..rows as i32 {
+        for col in 0..cols as i32 {
+            let pos = Posn { row, col };
+            match maze[&pos] {
+                Tile::Wall => {}
+                Tile::Exit => {
+                    exits.push(pos);
+                    continue;
+                }
+                Tile::Goal => {
+                    goals.push(pos);
+                    continue;
+                }
+                _ => continue,
+            }
+            match options.style {
+                WallStyle::Cells => {
+                    let _ = writeln!(svg, "{}", tile(maze, pos, size, "black"));
+                }
+                WallStyle::Lines => {
+                    let (x, y) = centre(maze, pos, size);
+                    // Each line once, and never across the seam of a wrapping maze.
+                    for next in maze.topology().neighbours(pos) {
+                        if next > pos && contains(&next) && maze[&next] == Tile::Wall {
+                            let (to_x, to_y) = centre(maze, next, size);
+                            let _ = writeln!(svg,
+                                             "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" \
+                                              y2=\"{:.1}\"/>",
+                                             x,
+                                             y,
+                                             to_x,
+                                             to_y);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    svg.push_str("</g>\n");
+    for pos in exits {
+        let _ = writeln!(svg, "{}", tile(maze, pos, size, "blue"));
+    }
+    for pos in goals {
+        let (x, y) = centre(maze, pos, size);
+        let _ = writeln!(svg,
+                         "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"purple\"/>",
+                         x,
+                         y,
+                         size / 4.0);
+    }
+
+    if let Some(&first) = route.first() {
+        let points: Vec<String> = route.iter()
+            .map(|&p| {
+                let (x, y) = centre(maze, p, size);
+                format!("{:.1},{:.1}", x, y)
+            })
+            .collect();
+        let (x, y) = centre(maze, first, size);
+        let _ = writeln!(svg,
+                         "<polyline points=\"{}\" fill=\"none\" stroke=\"green\" \
+                          stroke-width=\"{:.1}\" stroke-linejoin=\"round\"/>",
+                         points.join(" "),
+                         size / 4.0);
+        let _ = writeln!(svg,
+                         "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"green\"/>",
+                         x,
+                         y,
+                         size / 3.0);
+    }
+
+    if options.trolls {
+        for (&pos, troll) in &maze.trolls {
+            let fill = if troll.alive { "blue" } else { "red" };
+            let _ = writeln!(svg, "{}", arrow(maze, pos, troll.dir, size, fill));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[test]
+fn test_render_svg() {
+    use troll::Troll;
+
+    let mut maze = Maze::parse("#####\nX   #\n#####\n").unwrap();
+    maze.add_troll(Posn { row: 1, col: 3 }, Troll::new(Direction::West));
+    let route = [Posn { row: 1, col: 2 }, Posn { row: 1, col: 1 }, Posn { row: 1, col: 0 }];
+
+    let svg = render(&maze, &[], &SvgOptions::default());
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.ends_with("</svg>\n"));
+    // The background, 11 walls and the exit.
+    assert_eq!(svg.matches("<rect").count(), 1 + 11 + 1);
+    assert!(!svg.contains("<polyline"));
+    assert!(!svg.contains("<polygon"));
+
+    let options = SvgOptions {
+        style: WallStyle::Lines,
+        trolls: true,
+        ..SvgOptions::default()
+    };
+    let svg = render(&maze, &route, &options);
+    // Four along the top and bottom, two down the right hand side.
+    assert_eq!(svg.matches("<line").count(), 4 + 4 + 2);
+    assert!(svg.contains("<polyline points=\"25.0,15.0 15.0,15.0 5.0,15.0\""));
+    // Pointing left.
+    assert!(svg.contains("<polygon points=\"31.0,15.0"));
+
+    maze.shape = Shape::Hex;
+    assert_eq!(render(&maze, &[], &SvgOptions::default()).matches("<polygon").count(),
+               11 + 1);
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::io::prelude::*;
+use std::panic;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Once;
+
+use libc::{c_int, STDIN_FILENO, STDOUT_FILENO};
+use termios::*;
+
+const ENTER: &[u8] = b"\x1B[?1049h\x1B[1;1H\x1B[?25l";
+const LEAVE: &[u8] = b"\x1B[?1049l\x1B[?25h";
+
+/// The settings from before the game, kept where the signal handlers can
+/// reach them. Set once, and never freed.
+static SAVED: AtomicPtr<Termios> = AtomicPtr::new(ptr::null_mut());
+/// Whether the game has the terminal now.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Whether the game was stopped while it had the terminal.
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+/// Whether the game has come back from being stopped, so the screen needs
+/// drawing again.
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+static HOOKS: Once = Once::new();
+
+/// The game's hold on the terminal: the alternate screen, with no cursor,
+/// no echo and no waiting for enter. Everything goes back the way it was
+/// when it's dropped, after a panic, and on Ctrl-C, `SIGTERM` or Ctrl-Z. It
+/// comes back after a `SIGCONT`.
+pub struct Terminal {
+    _private: (),
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Terminal> {
+        let termios = Termios::from_fd(STDIN_FILENO)?;
+        if SAVED.load(Ordering::SeqCst).is_null() {
+            SAVED.store(Box::into_raw(Box::new(termios)), Ordering::SeqCst);
+        }
+        HOOKS.call_once(install_hooks);
+        io::stdout().flush()?;
+        take_over();
+        Ok(Terminal { _private: () })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        give_back();
+    }
+}
+
+/// Whether the game was stopped and carried on since this was last asked.
+pub fn take_resumed() -> bool {
+    RESUMED.swap(false, Ordering::SeqCst)
+}
+
+fn write_out(bytes: &[u8]) {
+    // Not `print!`, which takes a lock, so this is safe in a signal handler.
+    unsafe {
+        libc::write(STDOUT_FILENO, bytes.as_ptr() as *const _, bytes.len());
+    }
+}
+
+fn take_over() {
+    let saved = SAVED.load(Ordering::SeqCst);
+    if saved.is_null() {
+        return;
+    }
+    let mut raw = unsafe { *saved };
+    raw.c_lflag = ISIG;
+    raw.c_cc[VTIME] = 0;
+    raw.c_cc[VMIN] = 1;
+    let _ = tcsetattr(STDIN_FILENO, TCSAFLUSH, &raw);
+    write_out(ENTER);
+    ACTIVE.store(true, Ordering::SeqCst);
+}
+
+/// Puts the terminal back, if the game has it. Returns whether it did.
+fn give_back() -> bool {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return false;
+    }
+    let saved = SAVED.load(Ordering::SeqCst);
+    if !saved.is_null() {
+        let _ = tcsetattr(STDIN_FILENO, TCSAFLUSH, unsafe { &*saved });
+    }
+    write_out(LEAVE);
+    true
+}
+
+fn handle(signal: c_int) {
+    unsafe {
+        let mut action: libc::sigaction = ::std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        // No SA_RESTART, so a read waiting for a key sees the signal.
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, ptr::null_mut());
+    }
+}
+
+extern "C" fn on_signal(signal: c_int) {
+    if signal == libc::SIGCONT {
+        if SUSPENDED.swap(false, Ordering::SeqCst) {
+            take_over();
+            RESUMED.store(true, Ordering::SeqCst);
+        }
+        handle(libc::SIGTSTP);
+        return;
+    }
+    let had_terminal = give_back();
+    if signal == libc::SIGTSTP {
+        SUSPENDED.store(had_terminal, Ordering::SeqCst);
+    }
+    // Do what the signal would have done: stop or die. It's held back
+    // until this handler returns.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+fn install_hooks() {
+    for &signal in &[libc::SIGINT, libc::SIGTERM, libc::SIGTSTP, libc::SIGCONT] {
+        handle(signal);
+    }
+    // Put the terminal back first, so the panic message can be read.
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = io::stdout().flush();
+        give_back();
+        previous(info);
+    }));
+}
@@ -1,25 +1,77 @@
 
 use std::fmt;
 
-use ansi_term::Colour::{Red, Blue};
 use ansi_term::Style;
 use ansi_term::ANSIString;
 
+use screen::{paint, paint_as, render_mode, theme, RenderMode};
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Tile {
     Floor,
     Wall,
     Exit,
+    /// Leads to the same spot on the floor above.
+    UpStairs,
+    /// Leads to the same spot on the floor below.
+    DownStairs,
+    /// Floor that a puzzle's exit wants covered with a wall.
+    Goal,
 }
 
+/// Every tile, in the order of their codes.
+static TILES: [Tile; 6] = [Tile::Floor,
+                           Tile::Wall,
+                           Tile::Exit,
+                           Tile::UpStairs,
+                           Tile::DownStairs,
+                           Tile::Goal];
+
 impl Tile {
+    /// A small number for the tile, for packing maps tightly.
+    pub fn code(&self) -> u8 {
+        match *self {
+            Tile::Floor => 0,
+            Tile::Wall => 1,
+            Tile::Exit => 2,
+            Tile::UpStairs => 3,
+            Tile::DownStairs => 4,
+            Tile::Goal => 5,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<&'static Tile> {
+        TILES.get(code as usize)
+    }
+
     pub fn coloured(&self) -> ANSIString<'static> {
+        self.coloured_as(render_mode())
+    }
+
+    pub fn coloured_as(&self, mode: RenderMode) -> ANSIString<'static> {
+        let (plain, theme) = (Style::new(), theme());
+        let paint = |colour, mono, text| paint_as(mode, colour, mono, text);
+        match *self {
+            Tile::Floor => paint(plain, plain, " "),
+            Tile::Wall => paint(theme.wall.normal(), plain, "#"),
+            Tile::Exit => paint(theme.exit.normal(), plain.bold(), "X"),
+            Tile::UpStairs => paint(theme.stairs.normal(), plain, "<"),
+            Tile::DownStairs => paint(theme.stairs.normal(), plain, ">"),
+            Tile::Goal => paint(theme.goal.normal(), plain, "."),
+        }
+    }
+
+    /// Whether players and trolls can stand here.
+    pub fn is_walkable(&self) -> bool {
         match *self {
-            Tile::Floor => Style::new().paint(" "),
-            Tile::Wall => Red.paint("#"),
-            Tile::Exit => Blue.paint("X"),
+            Tile::Floor | Tile::UpStairs | Tile::DownStairs | Tile::Goal => true,
+            Tile::Wall | Tile::Exit => false,
         }
     }
+
+    pub fn is_stairs(&self) -> bool {
+        *self == Tile::UpStairs || *self == Tile::DownStairs
+    }
 }
 
 
@@ -28,9 +80,9 @@ impl fmt::Display for Tile {
         write!(f,
                "{}",
                match *self {
-                   Tile::Floor => Red.paint(" "), // Less adjusting the colour
-                   Tile::Wall => Red.paint("#"),
-                   Tile::Exit => Blue.paint("X"),
+                   // Less adjusting the colour
+                   Tile::Floor => paint(theme().wall.normal(), Style::new(), " "),
+                   _ => self.coloured(),
                })
     }
 }
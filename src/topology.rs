@@ -0,0 +1,177 @@
+use std::fmt;
+use std::str::FromStr;
+
+use direction::Direction;
+use direction::Direction::*;
+use posn::Posn;
+
+/// How the tiles of a maze fit together: which directions you can move in,
+/// and where each tile is drawn on screen.
+///
+/// Mazes are always stored as a rectangle of rows and columns; the topology
+/// decides what's next to what.
+pub trait Topology {
+    /// Every direction you can move in, clockwise.
+    fn directions(&self) -> &'static [Direction];
+
+    /// The (row, col) offset of one step in each of `directions()`.
+    fn offsets(&self) -> &'static [(i32, i32)];
+
+    /// Where a tile is drawn, as (row, col) on the screen.
+    fn screen_position(&self, pos: Posn) -> (usize, usize);
+
+    /// How much of the screen a map of `rows` by `cols` tiles covers.
+    fn screen_size(&self, rows: usize, cols: usize) -> (usize, usize);
+
+    fn offset(&self, dir: Direction) -> Option<(i32, i32)> {
+        self.directions()
+            .iter()
+            .position(|&d| d == dir)
+            .map(|i| self.offsets()[i])
+    }
+
+    /// The direction whose step is `offset`, if there is one.
+    fn direction(&self, offset: (i32, i32)) -> Option<Direction> {
+        self.offsets()
+            .iter()
+            .position(|&o| o == offset)
+            .map(|i| self.directions()[i])
+    }
+
+    fn step(&self, pos: Posn, dir: Direction) -> Posn {
+        pos + self.offset(dir).expect("Direction isn't part of this topology")
+    }
+
+    fn neighbours(&self, pos: Posn) -> Neighbours {
+        Neighbours {
+            pos,
+            offsets: self.offsets(),
+            next: 0,
+        }
+    }
+}
+
+pub struct Neighbours {
+    pos: Posn,
+    offsets: &'static [(i32, i32)],
+    next: usize,
+}
+
+impl Iterator for Neighbours {
+    type Item = Posn;
+    fn next(&mut self) -> Option<Posn> {
+        let offset = self.offsets.get(self.next)?;
+        self.next += 1;
+        Some(self.pos + *offset)
+    }
+}
+
+/// The classic grid, with four neighbours and one character per tile.
+pub struct Square;
+
+const SQUARE_DIRECTIONS: [Direction; 4] = [North, East, South, West];
+const SQUARE_OFFSETS: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+impl Topology for Square {
+    fn directions(&self) -> &'static [Direction] {
+        &SQUARE_DIRECTIONS
+    }
+
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        &SQUARE_OFFSETS
+    }
+
+    fn screen_position(&self, pos: Posn) -> (usize, usize) {
+        (pos.row as usize, pos.col as usize)
+    }
+
+    fn screen_size(&self, rows: usize, cols: usize) -> (usize, usize) {
+        (rows, cols)
+    }
+}
+
+/// Pointy-topped hexagons in axial coordinates, with six neighbours.
+///
+/// Going down a row shifts half a hexagon to the right, so the map is drawn
+/// as a rhombus. Tiles are two characters apart on screen, which also evens
+/// out the terminal's tall characters.
+pub struct Hex;
+
+const HEX_DIRECTIONS: [Direction; 6] = [NorthEast, East, SouthEast, SouthWest, West, NorthWest];
+const HEX_OFFSETS: [(i32, i32); 6] = [(-1, 1), (0, 1), (1, 0), (1, -1), (0, -1), (-1, 0)];
+
+impl Topology for Hex {
+    fn directions(&self) -> &'static [Direction] {
+        &HEX_DIRECTIONS
+    }
+
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        &HEX_OFFSETS
+    }
+
+    fn screen_position(&self, pos: Posn) -> (usize, usize) {
+        (pos.row as usize, (2 * pos.col + pos.row) as usize)
+    }
+
+    fn screen_size(&self, rows: usize, cols: usize) -> (usize, usize) {
+        (rows, 2 * cols + rows - 2)
+    }
+}
+
+/// Names a `Topology`, so mazes can say which one they use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Square,
+    Hex,
+}
+
+impl Shape {
+    pub fn topology(&self) -> &'static dyn Topology {
+        match *self {
+            Shape::Square => &Square,
+            Shape::Hex => &Hex,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Shape::Square => "square",
+            Shape::Hex => "hex",
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Shape, String> {
+        match s {
+            "square" => Ok(Shape::Square),
+            "hex" => Ok(Shape::Hex),
+            _ => Err(format!("Unknown shape '{}', expected square or hex", s)),
+        }
+    }
+}
+
+#[test]
+fn test_hex_neighbours() {
+    let topology = Shape::Hex.topology();
+    let p = Posn { row: 3, col: 3 };
+    let ads: Vec<Posn> = topology.neighbours(p).collect();
+    assert_eq!(ads.len(), 6);
+    for (&dir, &n) in topology.directions().iter().zip(&ads) {
+        assert_eq!(topology.step(p, dir), n);
+        assert_eq!(topology.step(n, dir.flip()), p);
+        // Every neighbour is drawn touching the tile, one row away at most.
+        let (row, col) = topology.screen_position(p);
+        let (n_row, n_col) = topology.screen_position(n);
+        assert!((row as i32 - n_row as i32).abs() <= 1);
+        assert!((col as i32 - n_col as i32).abs() <= 2);
+    }
+    assert_eq!(topology.offset(Direction::North), None);
+}
@@ -0,0 +1,119 @@
+use rand::Rng;
+
+use maze::Maze;
+use posn::Posn;
+use tile::Tile;
+
+/// A tile on one floor of a `Tower`.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub floor: usize,
+    pub pos: Posn,
+}
+
+/// Mazes stacked on top of each other, all the same size.
+///
+/// Up stairs lead to the down stairs in the same spot on the floor above.
+/// Only the top floor has exits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tower {
+    pub floors: Vec<Maze>,
+}
+
+impl Tower {
+    /// Joins `floors` with a staircase between each floor and the next, and
+    /// takes the exits off every floor but the top one.
+    pub fn stack<R: Rng>(mut floors: Vec<Maze>, rng: &mut R) -> Result<Tower, String> {
+        assert!(!floors.is_empty());
+        for floor in 0..floors.len() - 1 {
+            if floors[floor].bounds() != floors[floor + 1].bounds() {
+                return Err("The floors of a tower have to be the same size".to_owned());
+            }
+            for exit in floors[floor].exits() {
+                floors[floor].set(&exit, Tile::Wall);
+            }
+            let (rows, cols) = floors[floor].bounds();
+            let mut spots = vec![];
+            for row in 0..rows as i32 {
+                for col in 0..cols as i32 {
+                    let pos = Posn { row, col };
+                    if floors[floor][&pos] == Tile::Floor &&
+                       floors[floor + 1][&pos] == Tile::Floor {
+                        spots.push(pos);
+                    }
+                }
+            }
+            let pos = *rng.choose(&spots)
+                .ok_or_else(|| format!("No room for stairs above floor {}", floor + 1))?;
+            info!("Stairs from floor {} at {:?}", floor + 1, pos);
+            floors[floor].set(&pos, Tile::UpStairs);
+            floors[floor + 1].set(&pos, Tile::DownStairs);
+        }
+        Ok(Tower { floors })
+    }
+
+    pub fn top(&self) -> usize {
+        self.floors.len() - 1
+    }
+
+    /// Where you end up after stepping onto `loc`, if it's a staircase.
+    pub fn stairs_destination(&self, loc: Location) -> Option<Location> {
+        let floor = match self.floors[loc.floor][&loc.pos] {
+            Tile::UpStairs if loc.floor < self.top() => loc.floor + 1,
+            Tile::DownStairs if loc.floor > 0 => loc.floor - 1,
+            _ => return None,
+        };
+        Some(Location {
+            floor,
+            pos: loc.pos,
+        })
+    }
+}
+
+impl From<Maze> for Tower {
+    fn from(maze: Maze) -> Tower {
+        Tower { floors: vec![maze] }
+    }
+}
+
+#[test]
+fn test_stairs_join_floors() {
+    use generator::Algorithm;
+    use pathfind::pathfind;
+    use rng::GameRng;
+    use topology::Shape;
+
+    let mut rng = GameRng::new(9);
+    let floors = (0..3)
+        .map(|_| Algorithm::Prim.generate(Shape::Square, 6, 6, false, &mut rng))
+        .collect();
+    let tower = Tower::stack(floors, &mut rng).unwrap();
+    assert!(tower.floors[0].exits().is_empty());
+    assert!(tower.floors[1].exits().is_empty());
+    assert_eq!(tower.floors[2].exits().len(), 1);
+
+    for floor in 0..tower.top() {
+        let (rows, cols) = tower.floors[floor].bounds();
+        for row in 0..rows as i32 {
+            for col in 0..cols as i32 {
+                let loc = Location {
+                    floor,
+                    pos: Posn { row, col },
+                };
+                if tower.floors[floor][&loc.pos] == Tile::UpStairs {
+                    let above = tower.stairs_destination(loc).unwrap();
+                    assert_eq!(above.floor, floor + 1);
+                    assert_eq!(tower.stairs_destination(above), Some(loc));
+                }
+            }
+        }
+    }
+
+    let start = Location {
+        floor: 0,
+        pos: tower.floors[0].random_floor_tile(&mut rng),
+    };
+    let path = pathfind(&tower, start);
+    assert_eq!(tower.floors[2][&path.last().unwrap().pos], Tile::Exit);
+    assert!(path.iter().any(|l| l.floor == 1));
+}
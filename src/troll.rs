@@ -1,14 +1,15 @@
 use std::fmt;
 use std::borrow::Cow;
+use std::str::FromStr;
 
-use rand::{Rand, Rng};
+use rand::Rng;
 
-use ansi_term::Colour::{Red, Blue};
-use ansi_term::ANSIString;
+use ansi_term::{ANSIString, Style};
 
 use direction::Direction;
 use maze::Maze;
 use posn::Posn;
+use screen::{paint_as, render_mode, theme, RenderMode};
 use tile::Tile;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -18,6 +19,33 @@ pub enum State {
     Stunned(i32),
 }
 
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            State::Wandering => write!(f, "wandering"),
+            State::Charging => write!(f, "charging"),
+            State::Stunned(counter) => write!(f, "stunned {}", counter),
+        }
+    }
+}
+
+impl FromStr for State {
+    type Err = String;
+    fn from_str(s: &str) -> Result<State, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words[..] {
+            ["wandering"] => Ok(State::Wandering),
+            ["charging"] => Ok(State::Charging),
+            ["stunned", counter] => {
+                counter.parse()
+                    .map(State::Stunned)
+                    .map_err(|_| format!("Bad stun counter '{}'", counter))
+            }
+            _ => Err(format!("Unknown troll state '{}'", s)),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Troll {
     pub dir: Direction,
@@ -31,49 +59,61 @@ impl fmt::Display for Troll {
     }
 }
 
-impl<'a> Into<Cow<'static, str>> for &'a Troll {
-    fn into(self) -> Cow<'static, str> {
-        Cow::Borrowed(self.dir.unicode())
+impl<'a> From<&'a Troll> for Cow<'static, str> {
+    fn from(troll: &'a Troll) -> Cow<'static, str> {
+        Cow::Borrowed(troll.dir.unicode())
     }
 }
 
 impl Troll {
     pub fn new(dir: Direction) -> Troll {
         Troll {
-            dir: dir,
+            dir,
             alive: true,
             state: State::Wandering,
         }
     }
 
-    pub fn coloured(&self) -> ANSIString {
+    pub fn coloured(&self) -> ANSIString<'static> {
+        self.coloured_as(render_mode())
+    }
+
+    pub fn coloured_as(&self, mode: RenderMode) -> ANSIString<'static> {
+        let glyph = match (mode, self.alive) {
+            (RenderMode::Ascii, true) => "T",
+            (RenderMode::Ascii, false) => "t",
+            _ => self.dir.unicode(),
+        };
         if self.alive {
-            Blue.paint(self)
+            paint_as(mode, theme().troll.normal(), Style::new(), glyph)
         } else {
-            Red.paint(self)
+            paint_as(mode, theme().dead_troll.normal(), Style::new().dimmed(), glyph)
         }
     }
+    /// Moves the troll one tick. `player_pos` is `None` when the player is
+    /// on another floor.
     pub fn update<R: Rng>(&mut self,
                           mut pos: Posn,
                           maze: &mut Maze,
-                          player_pos: Posn,
+                          player_pos: Option<Posn>,
                           rng: &mut R)
                           -> (Posn, bool) {
         if !self.alive {
             return (pos, false);
         }
+        let topology = maze.topology();
         self.state = match self.state {
             State::Wandering => {
-                let dir: Direction = rng.gen();
+                let dir = *rng.choose(topology.directions()).unwrap();
                 if self.dir == dir {
-                    let new_pos = pos + dir.numeric();
+                    let new_pos = maze.step(pos, dir);
                     if !maze.in_bounds(&new_pos) {
                         panic!("Troll wandered off the map");
                     }
-                    if new_pos == player_pos {
+                    if Some(new_pos) == player_pos {
                         return (new_pos, true);
                     }
-                    if maze[&new_pos] == Tile::Floor {
+                    if maze[&new_pos].is_walkable() {
                         pos = new_pos
                     }
                 } else {
@@ -82,30 +122,31 @@ impl Troll {
                 let mut probe_pos = pos;
                 let mut final_state = State::Wandering;
                 loop {
-                    probe_pos = probe_pos + dir.numeric();
-                    if !maze.in_bounds(&probe_pos) {
+                    probe_pos = maze.step(probe_pos, dir);
+                    // Looking all the way round a wrapping maze.
+                    if !maze.in_bounds(&probe_pos) || probe_pos == pos {
                         break;
                     }
-                    if probe_pos == player_pos {
+                    if Some(probe_pos) == player_pos {
                         final_state = State::Charging;
                         break;
                     }
-                    if !(maze[&probe_pos] == Tile::Floor) {
+                    if !maze[&probe_pos].is_walkable() {
                         break;
                     }
                 }
                 final_state
             }
             State::Charging => {
-                let new_pos = pos + self.dir.numeric();
+                let new_pos = maze.step(pos, self.dir);
                 if !maze.in_bounds(&new_pos) {
                     panic!("Troll charged off the map");
                 }
-                if new_pos == player_pos {
+                if Some(new_pos) == player_pos {
                     return (new_pos, true);
                 }
                 match maze[&new_pos] {
-                    Tile::Floor => {
+                    Tile::Floor | Tile::Goal | Tile::UpStairs | Tile::DownStairs => {
                         pos = new_pos;
                         State::Charging
                     }
@@ -129,9 +170,3 @@ impl Troll {
         (pos, false)
     }
 }
-
-impl Rand for Troll {
-    fn rand<R: Rng>(rng: &mut R) -> Self {
-        Troll::new(rng.gen())
-    }
-}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::str;
+
+use level::Level;
+use maze::binary::{self, BinaryError};
+use maze::{LoadError, Maze, MazeParseError, ParseErrorKind};
+use pathfind::distances;
+use pnm::{self, PnmError};
+use posn::Posn;
+use sokoban;
+
+/// Everything wrong with a maze or level file, in the order it's found,
+/// with the line and column of each problem.
+///
+/// On top of what stops a file loading, this checks that the exits are in
+/// the outer wall and that every floor tile can get to one of them. A
+/// problem with the header means the map can't be read, so that's the only
+/// one reported.
+pub fn validate(text: &str) -> Vec<MazeParseError> {
+    let draft = match Level::read(text) {
+        Ok(draft) => draft,
+        Err(e) => return vec![e],
+    };
+    let (first_line, mut problems) = (draft.first_line, draft.problems);
+    problems.extend(check(&draft.level.maze, first_line));
+    problems
+}
+
+/// Checks that the exits are in the outer wall and that every floor tile
+/// can get to one of them. The map starts on line `first_line`.
+fn check(maze: &Maze, first_line: usize) -> Vec<MazeParseError> {
+    let at = |pos: Posn, kind| {
+        MazeParseError {
+            line: first_line + pos.row as usize,
+            column: pos.col as usize + 1,
+            kind,
+        }
+    };
+
+    let mut problems = vec![];
+    let exits = maze.exits();
+    for &exit in &exits {
+        if !maze.on_border(exit) {
+            problems.push(at(exit, ParseErrorKind::ExitNotOnBorder));
+        }
+    }
+    if !exits.is_empty() {
+        let dist = distances(maze, &exits);
+        let (rows, cols) = maze.bounds();
+        for row in 0..rows as i32 {
+            for col in 0..cols as i32 {
+                let pos = Posn { row, col };
+                if maze[&pos].is_walkable() && dist[pos].is_none() {
+                    problems.push(at(pos, ParseErrorKind::Unreachable));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Validates any file a maze can be loaded from, the way `Level::from_file`
+/// and `sokoban::from_file` tell them apart. It's only an error if the file
+/// can't be read at all.
+///
+/// An XSB collection is only checked for loading, as goals behind boxes
+/// would look unreachable.
+pub fn validate_file(filename: &str) -> io::Result<Vec<LoadError>> {
+    let mut bytes = vec![];
+    File::open(filename)?.read_to_end(&mut bytes)?;
+    validate_bytes(&bytes, sokoban::is_collection(filename))
+}
+
+/// Validates the contents of a file, which is an XSB `collection` if it's
+/// named like one.
+pub fn validate_bytes(bytes: &[u8], collection: bool) -> io::Result<Vec<LoadError>> {
+    if pnm::is_netpbm(bytes) {
+        return Ok(match pnm::parse(bytes) {
+            Ok(maze) => {
+                check(&maze, 1).into_iter().map(PnmError::Map).map(LoadError::Image).collect()
+            }
+            Err(e) => vec![LoadError::Image(e)],
+        });
+    }
+    if binary::is_binary(bytes) {
+        return Ok(match binary::read(bytes) {
+            Ok(maze) => {
+                check(&maze, 1).into_iter().map(BinaryError::Map).map(LoadError::Binary).collect()
+            }
+            Err(e) => vec![LoadError::Binary(e)],
+        });
+    }
+    let text = str::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let problems = if collection {
+        sokoban::parse(text).err().into_iter().collect()
+    } else {
+        validate(text)
+    };
+    Ok(problems.into_iter().map(LoadError::Parse).collect())
+}
+
+#[test]
+fn test_validate() {
+    let problems = |text: &str| -> Vec<(usize, usize, ParseErrorKind)> {
+        validate(text).into_iter().map(|e| (e.line, e.column, e.kind)).collect()
+    };
+
+    assert_eq!(problems("#####\n#   X\n#####\n"), vec![]);
+    assert_eq!(problems("maze-level 1\n\n#####\n#   X\n#####\n"), vec![]);
+
+    let text = "#######\n\
+                # #   #\n\
+                ### X #\n\
+                #     \n\
+                #######\n";
+    assert_eq!(problems(text),
+               vec![(4,
+                     7,
+                     ParseErrorKind::Ragged {
+                         expected: 7,
+                         found: 6,
+                     }),
+                    (3, 5, ParseErrorKind::ExitNotOnBorder),
+                    (2, 2, ParseErrorKind::Unreachable)]);
+
+    assert_eq!(problems("#####\n     \n#####\n"),
+               vec![(2, 1, ParseErrorKind::MissingBorder),
+                    (2, 5, ParseErrorKind::MissingBorder),
+                    (1, 1, ParseErrorKind::NoExit)]);
+    assert_eq!(problems("maze-level 9\n\n###\n").len(), 1);
+}
+
+#[test]
+fn test_validate_bytes() {
+    let problems = |bytes: &[u8], collection: bool| -> Vec<String> {
+        validate_bytes(bytes, collection).unwrap().iter().map(|e| e.to_string()).collect()
+    };
+
+    let maze = Maze::parse("#####\n# # X\n#####\n").unwrap();
+    assert_eq!(problems(&binary::write(&maze), false),
+               vec!["row 2, column 2: no way from here to an exit"]);
+    assert_eq!(problems(b"P2 5 3 15\n0 0 0 0 0\n0 15 12 15 15\n0 0 0 0 0\n", false),
+               vec!["pixel row 2, column 5: gap in the outer wall"]);
+    let boxes = b"#####\n#@$.#\n#####\n";
+    assert_eq!(problems(boxes, true), Vec::<String>::new());
+    assert_eq!(problems(boxes, false)[0], "line 2, column 2: bad maze character '@'");
+    assert!(validate_bytes(b"#\xFF", false).is_err());
+}